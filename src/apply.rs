@@ -0,0 +1,221 @@
+use crate::{registry::Registry, tag::TagReport, validate::fetch_optional_digest};
+use anyhow::{anyhow, Context, Result};
+use oci_client::Reference;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, str::FromStr as _};
+
+/// A plan written by `tag --plan-out` that `apply` executes later on, but only if the registry is
+/// still in the state that the plan has been computed for.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApplyPlan {
+    /// The image whose manifest is copied.
+    #[serde(with = "reference_string")]
+    pub source: Reference,
+    /// The digest of the source image's manifest when the plan has been computed.
+    pub source_digest: String,
+    pub targets: Vec<PlannedTarget>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlannedTarget {
+    pub tag: String,
+    /// The digest that the tag pointed to when the plan has been computed or none if the tag did
+    /// not exist.
+    pub current_digest: Option<String>,
+}
+
+impl ApplyPlan {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Cannot write plan to {}.", path.display()))
+    }
+
+    /// Describes how the state of the registry differs from the state that the plan has been
+    /// computed for. The current digests of the targets must be in the order of the targets.
+    fn changes(&self, source_digest: &str, current_digests: &[Option<String>]) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        if self.source_digest != source_digest {
+            changes.push(format!(
+                "{} points to {source_digest} instead of {}",
+                self.source, self.source_digest
+            ));
+        }
+
+        for (target, current_digest) in self.targets.iter().zip(current_digests) {
+            if &target.current_digest != current_digest {
+                changes.push(format!(
+                    "{} points to {} instead of {}",
+                    target.tag,
+                    current_digest.as_deref().unwrap_or("nothing"),
+                    target.current_digest.as_deref().unwrap_or("nothing")
+                ));
+            }
+        }
+
+        changes
+    }
+}
+
+/// Parses the plan file given to the apply command.
+pub fn read_plan(path: &str) -> std::result::Result<ApplyPlan, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Cannot read plan {path}: {e}"))?;
+    serde_json::from_str(&content).map_err(|e| format!("Cannot parse plan {path}: {e}"))
+}
+
+/// Records the digests of the source image and of the tags to push so that [`apply`] can detect
+/// if the registry has been changed in the meantime.
+pub async fn plan<R: Registry>(
+    registry: &R,
+    image: &Reference,
+    tags_to_push: Vec<String>,
+) -> Result<ApplyPlan> {
+    let source_digest = registry
+        .resolve_digest(image)
+        .await
+        .with_context(|| format!("Cannot fetch digest of {image}"))?
+        .ok_or_else(|| anyhow!("{image} does not exist"))?;
+
+    let mut targets = Vec::with_capacity(tags_to_push.len());
+    for tag in tags_to_push {
+        let current_digest = fetch_optional_digest(registry, image, &tag).await?;
+        targets.push(PlannedTarget {
+            tag,
+            current_digest,
+        });
+    }
+
+    Ok(ApplyPlan {
+        source: image.clone(),
+        source_digest,
+        targets,
+    })
+}
+
+/// Pushes the tags of the plan if neither the source image nor any of the tags have been changed
+/// since the plan has been computed. The manifest is pulled by the planned digest so that a
+/// concurrent push of the source image cannot sneak in.
+pub async fn apply<R: Registry>(
+    registry: &R,
+    plan: &ApplyPlan,
+    dry_run: bool,
+) -> Result<TagReport> {
+    let source_digest = registry
+        .resolve_digest(&plan.source)
+        .await
+        .with_context(|| format!("Cannot fetch digest of {}", plan.source))?
+        .unwrap_or_else(|| String::from("nothing"));
+
+    let mut current_digests = Vec::with_capacity(plan.targets.len());
+    for target in &plan.targets {
+        current_digests.push(fetch_optional_digest(registry, &plan.source, &target.tag).await?);
+    }
+
+    let changes = plan.changes(&source_digest, &current_digests);
+    if !changes.is_empty() {
+        return Err(anyhow!(
+            "The registry has been changed since the plan has been computed:\n{}",
+            changes.join("\n")
+        ));
+    }
+
+    let source = Reference::from_str(&format!(
+        "{}/{}@{}",
+        plan.source.registry(),
+        plan.source.repository(),
+        plan.source_digest
+    ))
+    .expect("Must be valid image string");
+
+    crate::tag::tag(
+        registry,
+        &source,
+        plan.targets.iter().map(|t| t.tag.clone()).collect(),
+        dry_run,
+    )
+    .await
+}
+
+mod reference_string {
+    use oci_client::Reference;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(
+        reference: &Reference,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(reference)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Reference, D::Error> {
+        let reference = String::deserialize(deserializer)?;
+        Reference::from_str(&reference).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan() -> ApplyPlan {
+        ApplyPlan {
+            source: Reference::from_str("localhost:5000/postgres:16.8.0").unwrap(),
+            source_digest: String::from("sha256:abc"),
+            targets: vec![
+                PlannedTarget {
+                    tag: String::from("16"),
+                    current_digest: Some(String::from("sha256:def")),
+                },
+                PlannedTarget {
+                    tag: String::from("16.8"),
+                    current_digest: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn serialize_plan() {
+        let json = serde_json::to_value(plan()).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "source": "localhost:5000/postgres:16.8.0",
+                "source_digest": "sha256:abc",
+                "targets": [{
+                    "tag": "16",
+                    "current_digest": "sha256:def"
+                }, {
+                    "tag": "16.8",
+                    "current_digest": null
+                }]
+            })
+        );
+        assert_eq!(serde_json::from_value::<ApplyPlan>(json).unwrap(), plan());
+    }
+
+    #[test]
+    fn no_changes() {
+        assert!(plan()
+            .changes("sha256:abc", &[Some(String::from("sha256:def")), None])
+            .is_empty());
+    }
+
+    #[test]
+    fn detect_changed_source_and_targets() {
+        assert_eq!(
+            plan().changes("sha256:123", &[None, Some(String::from("sha256:456"))]),
+            vec![
+                String::from(
+                    "localhost:5000/postgres:16.8.0 points to sha256:123 instead of sha256:abc"
+                ),
+                String::from("16 points to nothing instead of sha256:def"),
+                String::from("16.8 points to sha256:456 instead of nothing"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::validate::is_manifest_unknown;
+use anyhow::{Context, Result};
+use futures_util::TryStreamExt as _;
+use oci_client::{
+    errors::OciDistributionError,
+    manifest::{
+        OciManifest, IMAGE_MANIFEST_LIST_MEDIA_TYPE, IMAGE_MANIFEST_MEDIA_TYPE,
+        OCI_IMAGE_INDEX_MEDIA_TYPE, OCI_IMAGE_MEDIA_TYPE,
+    },
+    secrets::RegistryAuth,
+    Client, Reference,
+};
+use std::{future::Future, pin::Pin, str::FromStr as _};
+
+/// The media types of the manifests that can be copied.
+const MANIFEST_MEDIA_TYPES: &[&str] = &[
+    OCI_IMAGE_MEDIA_TYPE,
+    OCI_IMAGE_INDEX_MEDIA_TYPE,
+    IMAGE_MANIFEST_MEDIA_TYPE,
+    IMAGE_MANIFEST_LIST_MEDIA_TYPE,
+];
+
+/// A manifest exactly as stored in the registry so that a copy has the same digest.
+#[derive(Clone, Debug)]
+pub struct RawManifest {
+    pub bytes: bytes::Bytes,
+    pub media_type: String,
+    pub digest: String,
+    pub manifest: OciManifest,
+}
+
+pub async fn pull_raw_manifest(
+    client: &Client,
+    registry_auth: &RegistryAuth,
+    image: &Reference,
+) -> Result<RawManifest> {
+    let (bytes, digest) = client
+        .pull_manifest_raw(image, registry_auth, MANIFEST_MEDIA_TYPES)
+        .await
+        .with_context(|| format!("Cannot pull manifest for {image}"))?;
+    let manifest = serde_json::from_slice::<OciManifest>(&bytes)
+        .with_context(|| format!("Cannot parse manifest of {image}"))?;
+
+    Ok(RawManifest {
+        bytes,
+        media_type: media_type(&manifest).to_string(),
+        digest,
+        manifest,
+    })
+}
+
+/// The media type is optional in OCI manifests, thus it is derived from the content if missing.
+fn media_type(manifest: &OciManifest) -> &str {
+    match manifest {
+        OciManifest::Image(image) => image.media_type.as_deref().unwrap_or(OCI_IMAGE_MEDIA_TYPE),
+        OciManifest::ImageIndex(index) => index
+            .media_type
+            .as_deref()
+            .unwrap_or(OCI_IMAGE_INDEX_MEDIA_TYPE),
+    }
+}
+
+/// Ensures that the blobs and, for an index, the child manifests that `manifest` references exist
+/// in the repository of `target` so that the registry doesn't reject pushing `manifest` to `target`
+/// with `MANIFEST_BLOB_UNKNOWN`. Missing blobs are mounted from the repository of `source` if both
+/// are in the same registry and uploaded otherwise.
+pub fn copy_references<'a>(
+    client: &'a Client,
+    source_auth: &'a RegistryAuth,
+    source: &'a Reference,
+    target_auth: &'a RegistryAuth,
+    target: &'a Reference,
+    manifest: &'a OciManifest,
+) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+    Box::pin(async move {
+        client
+            .store_auth_if_needed(target.resolve_registry(), target_auth)
+            .await;
+
+        match manifest {
+            OciManifest::Image(image) => {
+                for descriptor in std::iter::once(&image.config).chain(image.layers.iter()) {
+                    // Non-distributable layers are downloaded from their URLs by the clients.
+                    if descriptor
+                        .urls
+                        .as_ref()
+                        .is_some_and(|urls| !urls.is_empty())
+                    {
+                        continue;
+                    }
+
+                    let exists = client
+                        .blob_exists(target, &descriptor.digest)
+                        .await
+                        .with_context(|| {
+                            format!("Cannot check blob {} in {target}", descriptor.digest)
+                        })?;
+                    if !exists {
+                        copy_blob(client, source, target, &descriptor.digest).await?;
+                    }
+                }
+            }
+            OciManifest::ImageIndex(index) => {
+                for entry in &index.manifests {
+                    let child_target = by_digest(target, &entry.digest);
+                    match client
+                        .fetch_manifest_digest(&child_target, target_auth)
+                        .await
+                    {
+                        Ok(_) => continue,
+                        Err(err) if is_manifest_unknown(&err) => {}
+                        Err(err) => {
+                            return Err(err)
+                                .with_context(|| format!("Cannot check manifest {child_target}"))
+                        }
+                    }
+
+                    let child_source = by_digest(source, &entry.digest);
+                    let child = pull_raw_manifest(client, source_auth, &child_source).await?;
+                    copy_references(
+                        client,
+                        source_auth,
+                        &child_source,
+                        target_auth,
+                        &child_target,
+                        &child.manifest,
+                    )
+                    .await?;
+                    push_raw_manifest(client, &child_target, &child).await?;
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Pushes the manifest to `target` and returns its digest.
+pub(crate) async fn push_raw_manifest(
+    client: &Client,
+    target: &Reference,
+    manifest: &RawManifest,
+) -> Result<String> {
+    let content_type = manifest
+        .media_type
+        .parse()
+        .with_context(|| format!("Invalid media type {}", manifest.media_type))?;
+    client
+        .push_manifest_raw(target, manifest.bytes.clone(), content_type)
+        .await
+        .with_context(|| format!("Cannot push manifest to {target}"))?;
+
+    Ok(manifest.digest.clone())
+}
+
+async fn copy_blob(
+    client: &Client,
+    source: &Reference,
+    target: &Reference,
+    digest: &str,
+) -> Result<()> {
+    if source.resolve_registry() == target.resolve_registry()
+        && client.mount_blob(target, source, digest).await.is_ok()
+    {
+        return Ok(());
+    }
+
+    // The layers are streamed from the source to the target so that large images don't have to
+    // fit into memory.
+    let blob = client
+        .pull_blob_stream(source, digest)
+        .await
+        .with_context(|| format!("Cannot pull blob {digest} from {source}"))?;
+    let size = blob.content_length;
+    client
+        .push_blob_stream(
+            target,
+            blob.map_err(OciDistributionError::from),
+            digest,
+            size,
+        )
+        .await
+        .with_context(|| format!("Cannot push blob {digest} to {target}"))?;
+
+    Ok(())
+}
+
+/// The reference to the manifest with the given digest in the repository of `image`.
+fn by_digest(image: &Reference, digest: &str) -> Reference {
+    Reference::from_str(&format!(
+        "{}/{}@{digest}",
+        image.registry(),
+        image.repository()
+    ))
+    .expect("Must be valid image string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_media_type_of_docker_manifest_list() {
+        let manifest = serde_json::from_value::<OciManifest>(serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.list.v2+json",
+            "manifests": []
+        }))
+        .unwrap();
+
+        assert_eq!(media_type(&manifest), IMAGE_MANIFEST_LIST_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn derive_media_type_of_oci_index_without_media_type() {
+        let manifest = serde_json::from_value::<OciManifest>(serde_json::json!({
+            "schemaVersion": 2,
+            "manifests": [{
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "digest": "sha256:e692418e4cbaf90ca69d05a66403747baa33ee08806650b51fab815ad7fc331f",
+                "size": 7143
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(media_type(&manifest), OCI_IMAGE_INDEX_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn derive_media_type_of_oci_image_without_media_type() {
+        let manifest = serde_json::from_value::<OciManifest>(serde_json::json!({
+            "schemaVersion": 2,
+            "config": {
+                "mediaType": "application/vnd.oci.image.config.v1+json",
+                "digest": "sha256:b5b2b2c507a0944348e0303114d8d93aaaa081732b86451d9bce1f432a537bc7",
+                "size": 7023
+            },
+            "layers": []
+        }))
+        .unwrap();
+
+        assert_eq!(media_type(&manifest), OCI_IMAGE_MEDIA_TYPE);
+    }
+}
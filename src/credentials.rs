@@ -0,0 +1,398 @@
+use anyhow::{anyhow, Context, Result};
+use docker_credential::{CredentialRetrievalError, DockerCredential};
+use oci_client::secrets::RegistryAuth;
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+/// The key under which Docker stores the credentials of Docker Hub in its `config.json` and in
+/// credential helpers.
+const DOCKER_HUB_SERVER: &str = "https://index.docker.io/v1/";
+
+/// The user under which identity tokens are passed on until they are exchanged for an access token
+/// of the registry, the same convention as Docker's credential helpers use.
+pub(crate) const IDENTITY_TOKEN_USER: &str = "<token>";
+
+/// The message with which the credential helpers of docker-credential-helpers, e.g. Docker
+/// Desktop's, fail if they don't know the server.
+const CREDENTIALS_NOT_FOUND: &str = "credentials not found";
+
+/// Resolves the credentials for `registry` from the files that `docker login` and `podman login`
+/// maintain, invoking `docker-credential-*` helpers if the files refer to them. Docker's
+/// `config.json` (respecting `$DOCKER_CONFIG`) takes precedence over Podman's `auth.json`
+/// (respecting `$REGISTRY_AUTH_FILE` and `$XDG_RUNTIME_DIR`). If none of them knows the registry,
+/// the registry will be accessed anonymously. Identity tokens are OAuth2 refresh tokens, thus they
+/// are returned as password of the [`IDENTITY_TOKEN_USER`] and have to be exchanged at the
+/// registry's token service before accessing the registry.
+pub fn registry_auth_from_config_files(registry: &str) -> Result<RegistryAuth> {
+    registry_auth_from_files(
+        docker_config_path().as_deref(),
+        podman_auth_path().as_deref(),
+        registry,
+    )
+}
+
+fn registry_auth_from_files(
+    docker_config: Option<&Path>,
+    podman_auth: Option<&Path>,
+    registry: &str,
+) -> Result<RegistryAuth> {
+    let server = server_address(registry);
+
+    let credential = match credential_from_file(docker_config, server)? {
+        Some(credential) => Some(credential),
+        None => credential_from_file(podman_auth, server)?,
+    };
+
+    match credential {
+        None => Ok(RegistryAuth::Anonymous),
+        Some(credential) => Ok(to_registry_auth(credential)),
+    }
+}
+
+/// The credential for `server` from the file at `path`. A missing file or a file that doesn't know
+/// the server is no error, but a file that cannot be read or parsed is.
+fn credential_from_file(path: Option<&Path>, server: &str) -> Result<Option<DockerCredential>> {
+    let Some(path) = path.filter(|path| path.exists()) else {
+        return Ok(None);
+    };
+
+    let config = std::fs::read(path).with_context(|| format!("Cannot read {}.", path.display()))?;
+    match docker_credential::get_credential_from_reader(config.as_slice(), server) {
+        Ok(credential) => Ok(Some(credential)),
+        Err(CredentialRetrievalError::NoCredentialConfigured) => Ok(None),
+        Err(CredentialRetrievalError::ConfigReadError) => {
+            Err(anyhow!("Cannot parse {}.", path.display()))
+        }
+        Err(err) if helper_has_no_credential(&err, &config, server) => Ok(None),
+        Err(err) => Err(anyhow!(
+            "Cannot read credentials for {server} from {}: {err}",
+            path.display()
+        )),
+    }
+}
+
+/// If the credential helper failed because it doesn't know the server. Any failure of the
+/// `credsStore` helper, e.g. Docker Desktop's, counts as such if no `credHelpers` entry names a
+/// helper for the server, because the store is consulted for every registry, including public
+/// ones that are accessed anonymously.
+fn helper_has_no_credential(err: &CredentialRetrievalError, config: &[u8], server: &str) -> bool {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct CredHelpers {
+        cred_helpers: Option<HashMap<String, String>>,
+    }
+
+    match err {
+        CredentialRetrievalError::HelperFailure { stdout, stderr, .. }
+            if stdout.contains(CREDENTIALS_NOT_FOUND) || stderr.contains(CREDENTIALS_NOT_FOUND) =>
+        {
+            true
+        }
+        CredentialRetrievalError::HelperFailure { .. }
+        | CredentialRetrievalError::HelperCommunicationError => {
+            serde_json::from_slice::<CredHelpers>(config)
+                .ok()
+                .and_then(|config| config.cred_helpers)
+                .is_none_or(|helpers| helpers.get(server).is_none_or(String::is_empty))
+        }
+        _ => false,
+    }
+}
+
+fn docker_config_dir() -> Option<PathBuf> {
+    env::var_os("DOCKER_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".docker")))
+}
+
+/// `$DOCKER_CONFIG/config.json` or `~/.docker/config.json`, the same lookup as `docker login`.
+fn docker_config_path() -> Option<PathBuf> {
+    docker_config_dir().map(|dir| dir.join("config.json"))
+}
+
+/// The `auth.json` that `podman login` writes to, see
+/// <https://docs.podman.io/en/stable/markdown/podman-login.1.html#authfile-path>.
+fn podman_auth_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("REGISTRY_AUTH_FILE") {
+        return Some(PathBuf::from(path));
+    }
+
+    let primary_path = if cfg!(target_os = "linux") {
+        env::var_os("XDG_RUNTIME_DIR").map(|dir| Path::new(&dir).join("containers/auth.json"))
+    } else {
+        env::var_os("HOME").map(|home| Path::new(&home).join(".config/containers/auth.json"))
+    };
+
+    match primary_path {
+        Some(path) if path.is_file() => Some(path),
+        _ => docker_config_dir().map(|dir| dir.join("containers/auth.json")),
+    }
+}
+
+fn server_address(registry: &str) -> &str {
+    match registry {
+        "docker.io" | "index.docker.io" | "registry-1.docker.io" => DOCKER_HUB_SERVER,
+        registry => registry,
+    }
+}
+
+fn to_registry_auth(credential: DockerCredential) -> RegistryAuth {
+    match credential {
+        DockerCredential::UsernamePassword(user, password) => RegistryAuth::Basic(user, password),
+        DockerCredential::IdentityToken(token) => {
+            RegistryAuth::Basic(String::from(IDENTITY_TOKEN_USER), token)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry_auth_from_config(
+        config: serde_json::Value,
+        registry: &str,
+    ) -> Result<RegistryAuth> {
+        let credential = docker_credential::get_credential_from_reader(
+            config.to_string().as_bytes(),
+            server_address(registry),
+        )?;
+        Ok(to_registry_auth(credential))
+    }
+
+    /// docker:d
+    const DOCKER_AUTH: &str = "ZG9ja2VyOmQ=";
+    /// podman:p
+    const PODMAN_AUTH: &str = "cG9kbWFuOnA=";
+
+    /// A directory with the given config files that is removed when the test ends.
+    struct ConfigFiles(PathBuf);
+
+    impl ConfigFiles {
+        fn new(test: &str) -> Self {
+            let dir =
+                env::temp_dir().join(format!("oci-semver-tagging-{test}-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, content: impl ToString) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, content.to_string()).unwrap();
+            path
+        }
+    }
+
+    impl Drop for ConfigFiles {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn auths(registry: &str, auth: &str) -> serde_json::Value {
+        serde_json::json!({
+            "auths": {
+                registry: { "auth": auth }
+            }
+        })
+    }
+
+    #[test]
+    fn basic_auth_from_auths() {
+        assert_eq!(
+            registry_auth_from_config(
+                serde_json::json!({
+                    "auths": {
+                        "registry.example.com": {
+                            // foo:bar
+                            "auth": "Zm9vOmJhcg=="
+                        }
+                    }
+                }),
+                "registry.example.com"
+            )
+            .unwrap(),
+            RegistryAuth::Basic(String::from("foo"), String::from("bar"))
+        );
+    }
+
+    #[test]
+    fn basic_auth_for_docker_hub() {
+        assert_eq!(
+            registry_auth_from_config(
+                serde_json::json!({
+                    "auths": {
+                        "https://index.docker.io/v1/": {
+                            // foo:bar
+                            "auth": "Zm9vOmJhcg=="
+                        }
+                    }
+                }),
+                "docker.io"
+            )
+            .unwrap(),
+            RegistryAuth::Basic(String::from("foo"), String::from("bar"))
+        );
+    }
+
+    #[test]
+    fn keep_identity_token_for_exchange() {
+        assert_eq!(
+            registry_auth_from_config(
+                serde_json::json!({
+                    "auths": {
+                        "registry.example.com": {
+                            "identitytoken": "t0k3n"
+                        }
+                    }
+                }),
+                "registry.example.com"
+            )
+            .unwrap(),
+            RegistryAuth::Basic(String::from("<token>"), String::from("t0k3n"))
+        );
+    }
+
+    #[test]
+    fn prefer_docker_config_over_podman_auth_file() {
+        let files = ConfigFiles::new("prefer-docker");
+        let docker = files.write("config.json", auths("registry.example.com", DOCKER_AUTH));
+        let podman = files.write("auth.json", auths("registry.example.com", PODMAN_AUTH));
+
+        assert_eq!(
+            registry_auth_from_files(Some(&docker), Some(&podman), "registry.example.com").unwrap(),
+            RegistryAuth::Basic(String::from("docker"), String::from("d"))
+        );
+    }
+
+    #[test]
+    fn fall_back_to_podman_auth_file() {
+        let files = ConfigFiles::new("fall-back-to-podman");
+        let docker = files.write("config.json", auths("other.example.com", DOCKER_AUTH));
+        let podman = files.write("auth.json", auths("registry.example.com", PODMAN_AUTH));
+
+        assert_eq!(
+            registry_auth_from_files(Some(&docker), Some(&podman), "registry.example.com").unwrap(),
+            RegistryAuth::Basic(String::from("podman"), String::from("p"))
+        );
+        assert_eq!(
+            registry_auth_from_files(
+                Some(&files.0.join("missing.json")),
+                Some(&podman),
+                "registry.example.com"
+            )
+            .unwrap(),
+            RegistryAuth::Basic(String::from("podman"), String::from("p"))
+        );
+    }
+
+    #[test]
+    fn anonymous_without_config_files() {
+        let files = ConfigFiles::new("anonymous");
+
+        assert_eq!(
+            registry_auth_from_files(
+                Some(&files.0.join("config.json")),
+                None,
+                "registry.example.com"
+            )
+            .unwrap(),
+            RegistryAuth::Anonymous
+        );
+    }
+
+    #[test]
+    fn fail_on_unparsable_config_file() {
+        let files = ConfigFiles::new("unparsable");
+        let docker = files.write("config.json", "{ not json");
+        let podman = files.write("auth.json", auths("registry.example.com", PODMAN_AUTH));
+
+        assert_eq!(
+            registry_auth_from_files(Some(&docker), Some(&podman), "registry.example.com")
+                .unwrap_err()
+                .to_string(),
+            format!("Cannot parse {}.", docker.display())
+        );
+    }
+
+    #[test]
+    fn use_cred_helper_over_auths() {
+        let files = ConfigFiles::new("cred-helper");
+        let docker = files.write(
+            "config.json",
+            serde_json::json!({
+                "auths": {
+                    // foo:bar
+                    "registry.example.com": { "auth": "Zm9vOmJhcg==" }
+                },
+                "credHelpers": {
+                    "registry.example.com": "oci-semver-tagging-missing"
+                }
+            }),
+        );
+
+        assert_eq!(
+            registry_auth_from_files(Some(&docker), None, "registry.example.com")
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "Cannot read credentials for registry.example.com from {}: Unable to communicate with credential helper",
+                docker.display()
+            )
+        );
+    }
+
+    #[test]
+    fn ignore_creds_store_without_credentials() {
+        let files = ConfigFiles::new("creds-store");
+        let docker = files.write(
+            "config.json",
+            serde_json::json!({
+                "auths": {},
+                "credsStore": "oci-semver-tagging-missing"
+            }),
+        );
+        let podman = files.write("auth.json", auths("registry.example.com", PODMAN_AUTH));
+
+        assert_eq!(
+            registry_auth_from_files(Some(&docker), None, "registry.example.com").unwrap(),
+            RegistryAuth::Anonymous
+        );
+        assert_eq!(
+            registry_auth_from_files(Some(&docker), Some(&podman), "registry.example.com").unwrap(),
+            RegistryAuth::Basic(String::from("podman"), String::from("p"))
+        );
+    }
+
+    #[test]
+    fn ignore_cred_helper_without_credentials() {
+        let config = serde_json::json!({
+            "credHelpers": { "registry.example.com": "ecr-login" }
+        })
+        .to_string();
+        let not_found = CredentialRetrievalError::HelperFailure {
+            helper: String::from("docker-credential-ecr-login"),
+            stdout: String::from("credentials not found in native keychain\n"),
+            stderr: String::new(),
+        };
+        let denied = CredentialRetrievalError::HelperFailure {
+            helper: String::from("docker-credential-ecr-login"),
+            stdout: String::new(),
+            stderr: String::from("AccessDenied"),
+        };
+
+        assert!(helper_has_no_credential(
+            &not_found,
+            config.as_bytes(),
+            "registry.example.com"
+        ));
+        assert!(!helper_has_no_credential(
+            &denied,
+            config.as_bytes(),
+            "registry.example.com"
+        ));
+    }
+}
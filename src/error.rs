@@ -0,0 +1,182 @@
+use crate::validate::ValidationError;
+use oci_client::errors::OciDistributionError;
+use std::fmt::Display;
+
+/// The errors that [`crate::run`] fails with. Each kind of error maps to its own process exit
+/// code, see [`Error::exit_code`], so that wrapper scripts can tell e.g. an unreachable registry
+/// from inconsistent tags.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The credentials cannot be read or the registry refused them.
+    #[error("{0:#}")]
+    Authentication(anyhow::Error),
+    /// The registry cannot be reached or failed to answer a request.
+    #[error("{0:#}")]
+    Registry(anyhow::Error),
+    /// A version, an image tag or a plan cannot be parsed.
+    #[error("{0:#}")]
+    Parse(anyhow::Error),
+    /// Some of the tags could not be pushed. Each failed tag is given together with the registry's
+    /// error.
+    #[error(
+        "Not all tags of {image} could be pushed:\n{}",
+        failed_tags
+            .iter()
+            .map(|(tag, err)| format!("{tag}: {err:#}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )]
+    Push {
+        image: String,
+        failed_tags: Vec<(String, anyhow::Error)>,
+    },
+    /// The existing tags are not partial semver tagged correctly.
+    #[error("{}", lines(.0))]
+    Validation(Vec<ValidationError>),
+    /// Issues that the repair command reports but cannot repair automatically.
+    #[error("The following issues cannot be repaired automatically:\n{}", lines(.0))]
+    Unrepairable(Vec<ValidationError>),
+    /// The registry is inconsistent after the tags have been pushed.
+    #[error(
+        "The tags of {image} are inconsistent after the push:\n{}",
+        lines(errors)
+    )]
+    Verification {
+        image: String,
+        errors: Vec<ValidationError>,
+    },
+    /// A concurrent registry request panicked or has been cancelled.
+    #[error("A registry request has been aborted: {0}")]
+    Join(#[from] tokio::task::JoinError),
+    #[error("{0:#}")]
+    Other(anyhow::Error),
+}
+
+impl Error {
+    /// The process exit code for this error:
+    ///
+    /// | Code | Error                                                                   |
+    /// |------|-------------------------------------------------------------------------|
+    /// | 1    | [`Error::Other`]                                                        |
+    /// | 2    | [`Error::Parse`], like clap's usage errors                              |
+    /// | 3    | [`Error::Authentication`]                                               |
+    /// | 4    | [`Error::Registry`]                                                     |
+    /// | 5    | [`Error::Push`]                                                         |
+    /// | 6    | [`Error::Validation`], [`Error::Unrepairable`], [`Error::Verification`] |
+    /// | 70   | [`Error::Join`], i.e. an internal software error                        |
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Parse(_) => 2,
+            Self::Authentication(_) => 3,
+            Self::Registry(_) => 4,
+            Self::Push { .. } => 5,
+            Self::Validation(_) | Self::Unrepairable(_) | Self::Verification { .. } => 6,
+            Self::Join(_) => 70,
+        }
+    }
+}
+
+/// Classifies the errors of the internal functions by the registry errors in their chain. Errors
+/// that are an [`Error`] already are kept as they are.
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<Error>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+
+        let oci_error = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<OciDistributionError>());
+        let reqwest_error = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>());
+        match (oci_error, reqwest_error) {
+            (
+                Some(
+                    OciDistributionError::AuthenticationFailure(_)
+                    | OciDistributionError::UnauthorizedError { .. },
+                ),
+                _,
+            ) => Self::Authentication(err),
+            (Some(_), _) => Self::Registry(err),
+            (None, Some(reqwest_error))
+                if reqwest_error.status().is_some_and(|status| {
+                    status == reqwest::StatusCode::UNAUTHORIZED
+                        || status == reqwest::StatusCode::FORBIDDEN
+                }) =>
+            {
+                Self::Authentication(err)
+            }
+            (None, Some(_)) => Self::Registry(err),
+            (None, None) => Self::Other(err),
+        }
+    }
+}
+
+fn lines<T: Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn classify_registry_errors_by_their_cause() {
+        let unauthorized = Err::<(), _>(OciDistributionError::UnauthorizedError {
+            url: String::from("https://localhost:5000/v2/postgres/manifests/16"),
+        })
+        .context("Cannot pull manifest for postgres:16")
+        .unwrap_err();
+        let not_found = Err::<(), _>(OciDistributionError::ImageManifestNotFoundError(
+            String::from("postgres:16"),
+        ))
+        .context("Cannot pull manifest for postgres:16")
+        .unwrap_err();
+
+        assert_eq!(Error::from(unauthorized).exit_code(), 3);
+        assert_eq!(Error::from(not_found).exit_code(), 4);
+        assert_eq!(Error::from(anyhow::anyhow!("Missing tag")).exit_code(), 1);
+    }
+
+    #[test]
+    fn keep_errors_wrapped_into_anyhow() {
+        let err = anyhow::Error::from(Error::Push {
+            image: String::from("postgres:16.8.0"),
+            failed_tags: vec![(String::from("16"), anyhow::anyhow!("manifest invalid"))],
+        });
+
+        assert_eq!(Error::from(err).exit_code(), 5);
+    }
+
+    #[test]
+    fn report_registry_errors_of_failed_tags() {
+        let err = Error::Push {
+            image: String::from("localhost:5000/postgres"),
+            failed_tags: vec![
+                (
+                    String::from("localhost:5000/postgres:16"),
+                    anyhow::anyhow!("denied").context("Cannot push manifest"),
+                ),
+                (
+                    String::from("localhost:5000/postgres:16.8"),
+                    anyhow::anyhow!("manifest invalid"),
+                ),
+            ],
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "Not all tags of localhost:5000/postgres could be pushed:\n\
+             localhost:5000/postgres:16: Cannot push manifest: denied\n\
+             localhost:5000/postgres:16.8: manifest invalid"
+        );
+    }
+}
@@ -1,29 +1,67 @@
 use anyhow::{anyhow, Context, Result};
+pub use apply::{ApplyPlan, PlannedTarget};
 use clap::{Parser, ValueEnum};
-use oci_distribution::{
-    client::{ClientConfig, ClientProtocol},
+pub use copy::RawManifest;
+pub use error::Error;
+use oci_client::{
+    client::{Certificate, CertificateEncoding, ClientConfig, ClientProtocol},
     secrets::RegistryAuth,
-    Client, Reference,
+    Reference,
 };
 pub use partial_semver::PartialSemverVersion;
+pub use registry::{InMemoryRegistry, OciRegistry, Registry};
 use semver::Version;
-use std::str::FromStr;
+use std::{
+    collections::BTreeSet,
+    io::{IsTerminal, Read},
+    path::PathBuf,
+    str::FromStr,
+};
+pub use tag::{
+    plan_tags, tags_to_push, PlannedTag, TagDecision, TagPlan, TagReport, TagResult, TagStatus,
+};
+pub use tagger::{SemverTagger, TagOptions};
+pub use validate::{detect_miss_placed_tags, LatestTag, ValidationError};
 
+mod apply;
+mod copy;
+mod credentials;
+mod error;
 mod partial_semver;
+mod prune;
+mod registry;
+mod repair;
 mod tag;
+mod tagger;
 mod validate;
+mod verify;
 
 #[derive(Parser, Debug, PartialEq)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    /// The user that is able to login to the registry
+    /// The user that is able to login to the registry. Without a user, the credentials are looked
+    /// up in Docker's config.json or Podman's auth.json as maintained by `docker login`.
     #[arg(short, long)]
     user: Option<String>,
     // The protocol that the client should use to connect to the registry.
     #[arg(short, long, default_value = "https")]
     protocol: Protocol,
+    /// A registry (host and optional port) that will be accessed via plain HTTP while all other
+    /// registries are accessed via HTTPS. Can be specified multiple times.
+    #[arg(long = "insecure-registry", value_name = "HOST")]
+    insecure_registries: Vec<String>,
+    /// A PEM or DER encoded file with additional CA certificates that will be trusted when
+    /// connecting to the registry. Can be specified multiple times. Client certificates (mTLS) are
+    /// not supported.
+    #[arg(long = "ca-file", value_name = "PATH")]
+    ca_files: Vec<PathBuf>,
+    /// The format in which the results of tag and validate are written to stdout.
+    #[arg(short, long, default_value = "text")]
+    output: Output,
     #[command(flatten)]
     password: Password,
+    #[command(flatten)]
+    token: Token,
     #[command(subcommand)]
     sub_command: SubCommands,
 }
@@ -43,6 +81,42 @@ enum SubCommands {
         /// A prefix that will be put in front of the tags to be pushed.
         #[arg(short, long)]
         tag_prefix: Option<String>,
+        /// A suffix that will be put behind the tags to be pushed, e.g. -alpine for variants of the
+        /// image. Each variant gets its own set of partial tags such as 1-alpine and 1.2-alpine.
+        #[arg(long, allow_hyphen_values = true)]
+        tag_suffix: Option<String>,
+        /// Moves the given tag, or latest if no tag name is given, to the version to tag if it is
+        /// the highest stable version of the repository. Prefix and suffix are not applied.
+        #[arg(
+            long,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "latest",
+            value_name = "TAG"
+        )]
+        latest: Option<String>,
+        /// If the version to tag is a pre-release, e.g. 2.0.0-rc.1, the moving channel tags 2.0-rc
+        /// and 2-rc will be pushed as well. Pre-releases never move the stable partial tags.
+        #[arg(long, default_value = "false")]
+        pre_release_channels: bool,
+        /// Fails without pushing anything if a moving tag such as the major or major.minor tag
+        /// would not point to the version to tag because a newer version owns it.
+        #[arg(long, default_value = "false")]
+        require_moving_tags: bool,
+        /// A full version that has been withdrawn, e.g. by the rollback command. It never owns the
+        /// moving tags, so that tagging doesn't move them back to it. Can be specified multiple
+        /// times.
+        #[arg(long = "yanked", value_name = "VERSION")]
+        yanked: Vec<Version>,
+        /// Writes the plan of what would be pushed, including the digests that the source image
+        /// and the tags currently point to, to the given file instead of pushing. The plan can be
+        /// executed with the apply command.
+        #[arg(long, value_name = "PATH")]
+        plan_out: Option<PathBuf>,
+        /// Skips reading the pushed tags back from the registry and checking the consistency of
+        /// the affected major version afterwards.
+        #[arg(long, default_value = "false")]
+        no_verify: bool,
         /// If the tool only outputs only what it would push.
         #[arg(short, long, default_value = "false")]
         dry_run: bool,
@@ -54,7 +128,201 @@ enum SubCommands {
         /// A prefix that will be put in front of the tags to be validated.
         #[arg(short, long)]
         tag_prefix: Option<String>,
+        /// A suffix that will be put behind the tags to be validated, e.g. -alpine for variants of
+        /// the image.
+        #[arg(long, allow_hyphen_values = true)]
+        tag_suffix: Option<String>,
+        /// Validates that the given tag, or latest if no tag name is given, points to the highest
+        /// stable version of the repository.
+        #[arg(
+            long,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "latest",
+            value_name = "TAG"
+        )]
+        latest: Option<String>,
+        /// A full version that has been withdrawn, e.g. by the rollback command. Partial tags must
+        /// not point to it unless all versions of their range have been withdrawn. Can be
+        /// specified multiple times.
+        #[arg(long = "yanked", value_name = "VERSION")]
+        yanked: Vec<Version>,
+    },
+    /// Repairs the partial semver tags that the validate command reports as missing or misplaced
+    /// by pushing the manifest of the correct full version under them.
+    ///
+    /// Partial tags pointing to a manifest that no full version tag points to, e.g. a hotfix pushed
+    /// by hand, are reported but not re-pointed since the manifest would be left untagged.
+    Repair {
+        /// The image of which the partial semver tags shall be repaired
+        image: Reference,
+        /// A prefix that will be put in front of the tags to be repaired.
+        #[arg(short, long)]
+        tag_prefix: Option<String>,
+        /// A suffix that will be put behind the tags to be repaired, e.g. -alpine for variants of
+        /// the image.
+        #[arg(long, allow_hyphen_values = true)]
+        tag_suffix: Option<String>,
+        /// Repairs the given tag, or latest if no tag name is given, so that it points to the
+        /// highest stable version of the repository.
+        #[arg(
+            long,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "latest",
+            value_name = "TAG"
+        )]
+        latest: Option<String>,
+        /// A full version that has been withdrawn, e.g. by the rollback command. Partial tags must
+        /// not point to it unless all versions of their range have been withdrawn. Can be
+        /// specified multiple times.
+        #[arg(long = "yanked", value_name = "VERSION")]
+        yanked: Vec<Version>,
+        /// If the tool only outputs only what it would push.
+        #[arg(short, long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Re-points the partial tags that point to a bad release to the next-highest remaining
+    /// version without deleting the release. Pass the version as --yanked to tag, promote, validate
+    /// and repair afterwards so that they keep the rolled back tags.
+    Rollback {
+        /// The image of which the partial semver tags shall be rolled back
+        image: Reference,
+        /// The full version that is withdrawn.
+        version: Version,
+        /// A prefix that will be put in front of the tags to be rolled back.
+        #[arg(short, long)]
+        tag_prefix: Option<String>,
+        /// A suffix that will be put behind the tags to be rolled back, e.g. -alpine for variants
+        /// of the image.
+        #[arg(long, allow_hyphen_values = true)]
+        tag_suffix: Option<String>,
+        /// Rolls back the given tag, or latest if no tag name is given, as well if it points to the
+        /// withdrawn version.
+        #[arg(
+            long,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "latest",
+            value_name = "TAG"
+        )]
+        latest: Option<String>,
+        /// A full version that has been withdrawn by an earlier rollback. The partial tags are not
+        /// rolled back to it but to the highest version that hasn't been withdrawn. Can be
+        /// specified multiple times.
+        #[arg(long = "yanked", value_name = "VERSION")]
+        yanked: Vec<Version>,
+        /// If the tool only outputs only what it would push.
+        #[arg(short, long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Deletes the manifests of full versions that fall outside of the retention policy together
+    /// with the partial tags of major and minor lines that keep no version. Manifests that are
+    /// still referenced by surviving tags, e.g. partial tags of kept lines or latest, are never
+    /// deleted. Pre-releases are never pruned.
+    Prune {
+        /// The image of which the full versions shall be pruned
+        image: Reference,
+        /// A prefix that will be put in front of the tags to be pruned.
+        #[arg(short, long)]
+        tag_prefix: Option<String>,
+        /// A suffix that will be put behind the tags to be pruned, e.g. -alpine for variants of the
+        /// image.
+        #[arg(long, allow_hyphen_values = true)]
+        tag_suffix: Option<String>,
+        /// The number of major versions to keep. If not specified, all major versions are kept.
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        keep_majors: Option<usize>,
+        /// The number of minor versions to keep per major version. If not specified, all minor
+        /// versions are kept.
+        #[arg(
+            long,
+            value_name = "M",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        keep_minors: Option<usize>,
+        /// The number of patch versions to keep per minor version. If not specified, all patch
+        /// versions are kept.
+        #[arg(
+            long,
+            value_name = "K",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        keep_patches: Option<usize>,
+        /// If the tool only outputs which versions it would keep and delete.
+        #[arg(short, long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Copies an image together with its blobs and the child manifests of an index into another
+    /// repository, e.g. from a staging to a production registry, and tags it there with the partial
+    /// semver tags computed from the destination's existing tags.
+    Promote {
+        /// The image that shall be promoted, tagged with its full version.
+        source: Reference,
+        /// The repository that the image shall be promoted to.
+        destination: Reference,
+        /// A prefix that will be put in front of the tags to be pushed.
+        #[arg(short, long)]
+        tag_prefix: Option<String>,
+        /// A suffix that will be put behind the tags to be pushed, e.g. -alpine for variants of the
+        /// image.
+        #[arg(long, allow_hyphen_values = true)]
+        tag_suffix: Option<String>,
+        /// Moves the given tag, or latest if no tag name is given, to the promoted version if it is
+        /// the highest stable version of the destination. Prefix and suffix are not applied.
+        #[arg(
+            long,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "latest",
+            value_name = "TAG"
+        )]
+        latest: Option<String>,
+        /// If the promoted version is a pre-release, e.g. 2.0.0-rc.1, the moving channel tags
+        /// 2.0-rc and 2-rc will be pushed as well.
+        #[arg(long, default_value = "false")]
+        pre_release_channels: bool,
+        /// A full version that has been withdrawn, e.g. by the rollback command. It never owns the
+        /// moving tags, so that tagging doesn't move them back to it. Can be specified multiple
+        /// times.
+        #[arg(long = "yanked", value_name = "VERSION")]
+        yanked: Vec<Version>,
+        /// Skips reading the pushed tags back from the registry and checking the consistency of
+        /// the affected major version afterwards.
+        #[arg(long, default_value = "false")]
+        no_verify: bool,
+        /// If the tool only outputs only what it would push.
+        #[arg(short, long, default_value = "false")]
+        dry_run: bool,
     },
+    /// Pushes the tags of a plan written by tag --plan-out. Refuses to push if the source image or
+    /// any of the tags point to other digests than when the plan has been computed.
+    Apply {
+        /// The plan file written by tag --plan-out.
+        #[arg(value_name = "PLAN", value_parser = apply::read_plan)]
+        plan: apply::ApplyPlan,
+        /// If the tool only outputs only what it would push.
+        #[arg(short, long, default_value = "false")]
+        dry_run: bool,
+    },
+}
+
+impl SubCommands {
+    fn image(&self) -> &Reference {
+        match self {
+            Self::Tag { image, .. } => image,
+            Self::Validate { image, .. } => image,
+            Self::Repair { image, .. } => image,
+            Self::Rollback { image, .. } => image,
+            Self::Prune { image, .. } => image,
+            Self::Promote { source, .. } => source,
+            Self::Apply { plan, .. } => &plan.source,
+        }
+    }
 }
 
 #[derive(clap::Args, Debug, PartialEq)]
@@ -68,35 +336,186 @@ struct Password {
     env: Option<String>,
 }
 
+#[derive(clap::Args, Debug, PartialEq)]
+#[group(required = false, multiple = false)]
+struct Token {
+    /// A bearer token issued by the registry's token service will be read from stdin
+    #[arg(id = "token-stdin", long = "token-stdin")]
+    stdin: bool,
+    /// A bearer token issued by the registry's token service will be read from the specified
+    /// environment variable
+    #[arg(id = "token-env", long = "token-env")]
+    env: Option<String>,
+}
+
 #[derive(PartialEq, Debug, Clone, ValueEnum)]
 enum Protocol {
     Https,
     Http,
 }
 
+#[derive(PartialEq, Debug, Clone, ValueEnum)]
+enum Output {
+    Text,
+    Json,
+}
+
 impl Args {
-    fn registry_auth(&self) -> Result<RegistryAuth> {
+    fn client_config(&self) -> Result<ClientConfig> {
+        let protocol = match &self.protocol {
+            Protocol::Http => ClientProtocol::Http,
+            Protocol::Https if self.insecure_registries.is_empty() => ClientProtocol::Https,
+            Protocol::Https => ClientProtocol::HttpsExcept(self.insecure_registries.clone()),
+        };
+
+        let mut extra_root_certificates = Vec::new();
+        for ca_file in &self.ca_files {
+            let data = std::fs::read(ca_file)
+                .with_context(|| format!("Cannot read CA file {}.", ca_file.display()))?;
+            extra_root_certificates.extend(certificates(data));
+        }
+
+        Ok(ClientConfig {
+            protocol,
+            extra_root_certificates,
+            ..Default::default()
+        })
+    }
+
+    /// The credentials for another registry than the one of the subcommand's image, e.g. for the
+    /// destination of promote. Credentials given on the command line apply to all registries while
+    /// Docker's and Podman's config files are consulted per registry.
+    fn other_registry_auth(
+        &self,
+        registry_auth: &RegistryAuth,
+        registry: &str,
+    ) -> Result<RegistryAuth> {
+        let explicit_credentials = self.user.is_some()
+            || self.password.stdin
+            || self.password.env.is_some()
+            || self.token.stdin
+            || self.token.env.is_some();
+
+        if explicit_credentials || registry == self.sub_command.image().registry() {
+            Ok(registry_auth.clone())
+        } else {
+            credentials::registry_auth_from_config_files(registry)
+        }
+    }
+
+    fn registry_auth(&self, registry: &str) -> Result<RegistryAuth> {
+        self.registry_auth_with_env(registry, |name| std::env::var(name))
+    }
+
+    /// Like [`Args::registry_auth`] but reads the environment variables of --password-env and
+    /// --token-env with `var`, so that tests don't have to modify the process' environment.
+    fn registry_auth_with_env(
+        &self,
+        registry: &str,
+        var: impl Fn(&str) -> std::result::Result<String, std::env::VarError>,
+    ) -> Result<RegistryAuth> {
+        if self.token.stdin || self.token.env.is_some() {
+            if self.user.is_some() || self.password.stdin || self.password.env.is_some() {
+                return Err(anyhow!(
+                    "A token cannot be combined with --user, --password-stdin or --password-env."
+                ));
+            }
+
+            return match (&self.token.stdin, &self.token.env) {
+                (true, None) => Ok(RegistryAuth::Bearer(read_secret_from_stdin(
+                    "token",
+                    "--token-stdin",
+                )?)),
+                (false, Some(env_var_name)) => {
+                    let token = var(env_var_name).with_context(|| {
+                        format!("Cannot read token from environment variable {env_var_name}.")
+                    })?;
+
+                    Ok(RegistryAuth::Bearer(token))
+                }
+                _ => Err(anyhow!(
+                    "--token-stdin and --token-env cannot be used together."
+                )),
+            };
+        }
+
         match (&self.user, &self.password.stdin, &self.password.env) {
-            (None, false, None) => Ok(RegistryAuth::Anonymous),
-            (Some(_user), true, None) => {
-                todo!()
+            (None, false, None) => credentials::registry_auth_from_config_files(registry),
+            (Some(user), true, None) => {
+                let password = read_secret_from_stdin("password", "--password-stdin")?;
+
+                Ok(RegistryAuth::Basic(user.clone(), password))
             }
             (Some(user), false, Some(env_var_name)) => {
-                let password = std::env::var(&env_var_name).with_context(|| {
+                let password = var(env_var_name).with_context(|| {
                     format!("Cannot read password from environment variable {env_var_name}.")
                 })?;
 
                 Ok(RegistryAuth::Basic(user.clone(), password))
             }
-            _ => Err(anyhow!("TODO")),
+            (Some(user), false, None) => Err(anyhow!(
+                "The user {user} requires a password provided by --password-stdin or --password-env."
+            )),
+            (None, true, None) => Err(anyhow!("--password-stdin requires --user.")),
+            (None, false, Some(_)) => Err(anyhow!("--password-env requires --user.")),
+            (_, true, Some(_)) => Err(anyhow!(
+                "--password-stdin and --password-env cannot be used together."
+            )),
         }
     }
 }
 
+/// Splits a CA bundle into its certificates because a PEM file may contain multiple certificates
+/// but the client only picks up the first one of each entry.
+fn certificates(data: Vec<u8>) -> Vec<Certificate> {
+    const PEM_END: &str = "-----END CERTIFICATE-----";
+
+    match std::str::from_utf8(&data) {
+        Ok(pem) if pem.contains(PEM_END) => pem
+            .split_inclusive(PEM_END)
+            .filter(|cert| cert.contains("-----BEGIN CERTIFICATE-----"))
+            .map(|cert| Certificate {
+                encoding: CertificateEncoding::Pem,
+                data: cert.trim().as_bytes().to_vec(),
+            })
+            .collect(),
+        _ => vec![Certificate {
+            encoding: CertificateEncoding::Der,
+            data,
+        }],
+    }
+}
+
+fn read_secret_from_stdin(secret_name: &str, flag: &str) -> Result<String> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(anyhow!(
+            "Refusing to read the {secret_name} from a terminal, pipe it into stdin when using {flag}."
+        ));
+    }
+
+    read_secret(stdin.lock(), secret_name)
+}
+
+fn read_secret(mut reader: impl Read, secret_name: &str) -> Result<String> {
+    let mut secret = String::new();
+    reader
+        .read_to_string(&mut secret)
+        .with_context(|| format!("Cannot read {secret_name} from stdin."))?;
+
+    let secret = secret.trim_end_matches(['\n', '\r']);
+    if secret.is_empty() {
+        return Err(anyhow!("The {secret_name} provided via stdin is empty."));
+    }
+
+    Ok(secret.to_string())
+}
+
 fn version_to_tag(
     image: &Reference,
     cli_version: Option<Version>,
     tag_prefix: &Option<String>,
+    tag_suffix: &Option<String>,
 ) -> Result<Version> {
     match cli_version {
         Some(version) => {
@@ -113,14 +532,15 @@ fn version_to_tag(
 
             let tag = match tag_prefix.as_ref() {
                 None => tag,
-                Some(prefix) => {
-                    if !tag.starts_with(prefix) {
-                        return Err(anyhow!(
-                            "The image tag {tag} doesn't start with the prefix {prefix}"
-                        ));
-                    }
-                    tag.trim_start_matches(prefix)
-                }
+                Some(prefix) => tag.strip_prefix(prefix.as_str()).ok_or_else(|| {
+                    anyhow!("The image tag {tag} doesn't start with the prefix {prefix}")
+                })?,
+            };
+            let tag = match tag_suffix.as_ref() {
+                None => tag,
+                Some(suffix) => tag.strip_suffix(suffix.as_str()).ok_or_else(|| {
+                    anyhow!("The image tag {tag} doesn't end with the suffix {suffix}")
+                })?,
             };
             Version::from_str(tag)
                 .with_context(|| format!("Can't parse version from image's tag which is {tag}"))
@@ -128,86 +548,526 @@ fn version_to_tag(
     }
 }
 
-async fn present_partial_semver_tags(
-    client: &Client,
-    registry_auth: &RegistryAuth,
+async fn present_partial_semver_tags<R: Registry>(
+    registry: &R,
     image: &Reference,
     prefix: &Option<String>,
+    suffix: &Option<String>,
 ) -> Result<Vec<PartialSemverVersion>> {
-    let tag_respones = client
-        .list_tags(image, registry_auth, None, None)
+    let tags = registry
+        .list_tags(image)
         .await
         .with_context(|| format!("Cannot resolve tags for {image}."))?;
 
-    Ok(tag_respones
-        .tags
-        .into_iter()
-        .flat_map(|tag| {
-            let tag = match prefix.as_ref() {
-                None => tag.as_str(),
-                Some(prefix) => {
-                    if !tag.starts_with(prefix) {
-                        return None;
-                    }
-                    tag.trim_start_matches(prefix)
-                }
-            };
-            PartialSemverVersion::from_str(tag).ok()
-        })
+    Ok(tags
+        .iter()
+        .filter_map(|tag| partial_semver_tag(tag, prefix, suffix))
         .collect())
 }
 
-pub async fn run(args: Args) -> Result<()> {
-    let client = Client::new(ClientConfig {
-        protocol: match &args.protocol {
-            Protocol::Https => ClientProtocol::Https,
-            Protocol::Http => ClientProtocol::Http,
-        },
-        ..Default::default()
-    });
+/// Parses the tag as partial semver version if it has the prefix and suffix.
+fn partial_semver_tag(
+    tag: &str,
+    prefix: &Option<String>,
+    suffix: &Option<String>,
+) -> Option<PartialSemverVersion> {
+    let tag = match prefix.as_ref() {
+        None => tag,
+        Some(prefix) => tag.strip_prefix(prefix.as_str())?,
+    };
+    let tag = match suffix.as_ref() {
+        None => tag,
+        Some(suffix) => tag.strip_suffix(suffix.as_str())?,
+    };
+    PartialSemverVersion::from_str(tag).ok()
+}
+
+fn print_validating(image: &Reference, existing_tags: &[PartialSemverVersion]) {
+    println!(
+        "Validating for {image} if the tags have correct partial semver tagging: {}",
+        existing_tags
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
+
+/// Runs the sub command. The errors are classified so that [`Error::exit_code`] tells the kind of
+/// failure.
+pub async fn run(args: Args) -> std::result::Result<(), Error> {
+    run_sub_command(args).await.map_err(Error::from)
+}
 
-    let registry_auth = args.registry_auth()?;
+async fn run_sub_command(args: Args) -> Result<()> {
+    let registry_auth = args
+        .registry_auth(args.sub_command.image().registry())
+        .map_err(Error::Authentication)?;
+    let registry = OciRegistry::from_config(args.client_config()?, registry_auth.clone())?
+        .exchange_identity_token(&[args.sub_command.image()])
+        .await?;
 
     match args.sub_command {
-        SubCommands::Validate { image, tag_prefix } => {
+        SubCommands::Validate {
+            image,
+            tag_prefix,
+            tag_suffix,
+            latest,
+            yanked,
+        } => {
+            let existing_tags = present_partial_semver_tags(
+                &registry,
+                &Reference::from_str(&format!("{}/{}", image.registry(), image.repository(),))
+                    .expect("Must be valid image string"),
+                &tag_prefix,
+                &tag_suffix,
+            )
+            .await?;
+
+            if args.output == Output::Text {
+                print_validating(&image, &existing_tags);
+            }
+
+            let errors = validate::validation_errors(
+                &registry,
+                &image,
+                &tag_prefix,
+                &tag_suffix,
+                &latest,
+                &yanked,
+                &existing_tags,
+            )
+            .await?;
+
+            if args.output == Output::Json {
+                let report = validate::ValidationReport {
+                    image: image.to_string(),
+                    tags: existing_tags.iter().map(ToString::to_string).collect(),
+                    errors: errors
+                        .iter()
+                        .map(|e| e.report(&tag_prefix, &tag_suffix))
+                        .collect(),
+                };
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::Validation(errors).into())
+            }
+        }
+        SubCommands::Repair {
+            image,
+            tag_prefix,
+            tag_suffix,
+            latest,
+            yanked,
+            dry_run,
+        } => {
             let existing_tags = present_partial_semver_tags(
-                &client,
-                &registry_auth,
+                &registry,
                 &Reference::from_str(&format!("{}/{}", image.registry(), image.repository(),))
                     .expect("Must be valid image string"),
                 &tag_prefix,
+                &tag_suffix,
+            )
+            .await?;
+
+            print_validating(&image, &existing_tags);
+
+            let errors = validate::validation_errors(
+                &registry,
+                &image,
+                &tag_prefix,
+                &tag_suffix,
+                &latest,
+                &yanked,
+                &existing_tags,
             )
             .await?;
 
-            validate::validate(&client, &registry_auth, &image, &tag_prefix, &existing_tags).await
+            let (actions, unrepairable) = repair::repair_actions(&errors, &tag_prefix, &tag_suffix);
+
+            repair::repair(
+                &registry,
+                &image,
+                &tag_prefix,
+                &tag_suffix,
+                actions,
+                dry_run,
+            )
+            .await?;
+
+            if unrepairable.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::Unrepairable(unrepairable.into_iter().cloned().collect()).into())
+            }
+        }
+        SubCommands::Rollback {
+            image,
+            version,
+            tag_prefix,
+            tag_suffix,
+            latest,
+            mut yanked,
+            dry_run,
+        } => {
+            let existing_tags = present_partial_semver_tags(
+                &registry,
+                &Reference::from_str(&format!("{}/{}", image.registry(), image.repository(),))
+                    .expect("Must be valid image string"),
+                &tag_prefix,
+                &tag_suffix,
+            )
+            .await?;
+
+            if !existing_tags.iter().any(|psv| psv == &version) {
+                return Err(anyhow!("There is no version {version} in {image}."));
+            }
+
+            yanked.push(version.clone());
+            let errors = validate::validation_errors(
+                &registry,
+                &image,
+                &tag_prefix,
+                &tag_suffix,
+                &latest,
+                &yanked,
+                &existing_tags,
+            )
+            .await?;
+
+            let actions = repair::rollback_actions(&errors, &version, &tag_prefix, &tag_suffix);
+            if actions.is_empty() {
+                println!("No tag needs to be rolled back from {version}");
+                return Ok(());
+            }
+
+            repair::repair(
+                &registry,
+                &image,
+                &tag_prefix,
+                &tag_suffix,
+                actions,
+                dry_run,
+            )
+            .await?;
+
+            println!("Pass --yanked {version} to tag, promote, validate and repair from now on.");
+
+            Ok(())
         }
         SubCommands::Tag {
             image,
             tag_version,
             tag_prefix,
+            tag_suffix,
+            latest,
+            pre_release_channels,
+            require_moving_tags,
+            yanked,
+            plan_out,
+            no_verify,
             dry_run,
         } => {
-            let version_to_tag = version_to_tag(&image, tag_version, &tag_prefix)?;
+            let version_to_tag = version_to_tag(&image, tag_version, &tag_prefix, &tag_suffix)
+                .map_err(Error::Parse)?;
 
             let existing_tags = present_partial_semver_tags(
-                &client,
-                &registry_auth,
+                &registry,
                 &Reference::from_str(&format!("{}/{}", image.registry(), image.repository(),))
                     .expect("Must be valid image string"),
                 &tag_prefix,
+                &tag_suffix,
             )
             .await?;
 
-            tag::tag(
-                &client,
-                &registry_auth,
-                &image,
+            let plan = tag::plan_tags(
+                version_to_tag.clone(),
                 &existing_tags,
-                version_to_tag,
                 &tag_prefix,
+                &tag_suffix,
+                &latest,
+                pre_release_channels,
+                &yanked,
+            );
+
+            if require_moving_tags {
+                let owned_by_newer_versions = plan
+                    .owned_by_newer_versions()
+                    .map(|(tag, newer_version)| {
+                        format!("{tag} is owned by the newer version {newer_version}")
+                    })
+                    .collect::<Vec<_>>();
+                if !owned_by_newer_versions.is_empty() {
+                    return Err(anyhow!(
+                        "Not all moving tags would point to {version_to_tag}:\n{}",
+                        owned_by_newer_versions.join("\n")
+                    ));
+                }
+            }
+
+            if let Some(plan_out) = &plan_out {
+                apply::plan(&registry, &image, plan.tags_to_push())
+                    .await?
+                    .write(plan_out)?;
+            }
+
+            let mut report = tag::tag(
+                &registry,
+                &image,
+                plan.tags_to_push(),
+                dry_run || plan_out.is_some(),
+            )
+            .await?;
+            report.plan = plan.tags;
+
+            let verification_errors = if !no_verify && !dry_run && plan_out.is_none() {
+                verify::verify(
+                    &registry,
+                    &image,
+                    &report,
+                    &version_to_tag,
+                    &tag_prefix,
+                    &tag_suffix,
+                    &latest,
+                    &yanked,
+                )
+                .await?
+            } else {
+                Vec::new()
+            };
+            report.verification_errors = verification_errors
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+
+            match args.output {
+                Output::Text => report.print(),
+                Output::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+
+            if let (Some(plan_out), Output::Text) = (&plan_out, &args.output) {
+                println!("Wrote plan to {}", plan_out.display());
+            }
+
+            if !report.is_success() {
+                Err(Error::Push {
+                    image: image.to_string(),
+                    failed_tags: report.failed_tags(),
+                }
+                .into())
+            } else if !verification_errors.is_empty() {
+                Err(Error::Verification {
+                    image: image.to_string(),
+                    errors: verification_errors,
+                }
+                .into())
+            } else {
+                Ok(())
+            }
+        }
+        SubCommands::Prune {
+            image,
+            tag_prefix,
+            tag_suffix,
+            keep_majors,
+            keep_minors,
+            keep_patches,
+            dry_run,
+        } => {
+            let repository =
+                Reference::from_str(&format!("{}/{}", image.registry(), image.repository(),))
+                    .expect("Must be valid image string");
+            // Deleting a manifest removes all of its tags, thus the tags that the retention policy
+            // doesn't consider, e.g. latest or other variants, must be known as well.
+            let mut existing_tags = Vec::new();
+            let mut other_tags = Vec::new();
+            for tag in registry
+                .list_tags(&repository)
+                .await
+                .with_context(|| format!("Cannot resolve tags for {repository}."))?
+            {
+                match partial_semver_tag(&tag, &tag_prefix, &tag_suffix) {
+                    Some(psv) => existing_tags.push(psv),
+                    None => other_tags.push(tag),
+                }
+            }
+
+            let digests = validate::fetch_digests(
+                &registry,
+                &image,
+                &tag_prefix,
+                &tag_suffix,
+                &existing_tags,
+            )
+            .await?;
+            let other_tags = validate::fetch_tag_digests(&registry, &image, other_tags).await?;
+
+            let plan = prune::prune_plan(
+                &digests,
+                &other_tags,
+                &prune::RetentionPolicy {
+                    majors: keep_majors,
+                    minors_per_major: keep_minors,
+                    patches_per_minor: keep_patches,
+                },
+            );
+            prune::print_plan(&plan, dry_run);
+
+            if dry_run {
+                return Ok(());
+            }
+
+            let digests_to_delete = plan
+                .iter()
+                .filter(|entry| entry.decision == prune::PruneDecision::Delete)
+                .map(|entry| entry.digest.as_str())
+                .collect::<BTreeSet<_>>();
+
+            let mut result = Ok(());
+            for digest in digests_to_delete {
+                let manifest = Reference::with_digest(
+                    image.registry().to_string(),
+                    image.repository().to_string(),
+                    digest.to_string(),
+                );
+                match registry.delete(&manifest).await {
+                    Ok(()) => println!("Deleted manifest {digest}"),
+                    Err(err) => {
+                        eprintln!("Cannot delete manifest {digest}: {err:#}");
+                        result = Err(Error::Registry(anyhow!(
+                            "Not all manifests of {image} could be deleted."
+                        ))
+                        .into());
+                    }
+                }
+            }
+
+            result
+        }
+        // The fields are borrowed because the credentials of the destination are resolved as well.
+        SubCommands::Promote {
+            ref source,
+            ref destination,
+            ref tag_prefix,
+            ref tag_suffix,
+            ref latest,
+            pre_release_channels,
+            ref yanked,
+            no_verify,
+            dry_run,
+        } => {
+            // Blobs are mounted from the source if both are in the same registry, which requires
+            // access to both repositories.
+            let repositories = if source.resolve_registry() == destination.resolve_registry() {
+                vec![destination, source]
+            } else {
+                vec![destination]
+            };
+            let destination_registry = registry
+                .with_auth(args.other_registry_auth(&registry_auth, destination.registry())?)
+                .exchange_identity_token(&repositories)
+                .await?;
+            let destination = Reference::from_str(&format!(
+                "{}/{}",
+                destination.registry(),
+                destination.repository(),
+            ))
+            .expect("Must be valid image string");
+
+            let version =
+                version_to_tag(source, None, tag_prefix, tag_suffix).map_err(Error::Parse)?;
+
+            let existing_tags = present_partial_semver_tags(
+                &destination_registry,
+                &destination,
+                tag_prefix,
+                tag_suffix,
+            )
+            .await?;
+
+            let plan = tag::plan_tags(
+                version.clone(),
+                &existing_tags,
+                tag_prefix,
+                tag_suffix,
+                latest,
+                pre_release_channels,
+                yanked,
+            );
+
+            let mut report = tag::tag_into(
+                &registry,
+                source,
+                &destination_registry,
+                &destination,
+                plan.tags_to_push(),
                 dry_run,
             )
-            .await
+            .await?;
+            report.plan = plan.tags;
+
+            let verification_errors = if !no_verify && !dry_run {
+                verify::verify(
+                    &destination_registry,
+                    &destination,
+                    &report,
+                    &version,
+                    tag_prefix,
+                    tag_suffix,
+                    latest,
+                    yanked,
+                )
+                .await?
+            } else {
+                Vec::new()
+            };
+            report.verification_errors = verification_errors
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+
+            match args.output {
+                Output::Text => report.print(),
+                Output::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+
+            if !report.is_success() {
+                Err(Error::Push {
+                    image: destination.to_string(),
+                    failed_tags: report.failed_tags(),
+                }
+                .into())
+            } else if !verification_errors.is_empty() {
+                Err(Error::Verification {
+                    image: destination.to_string(),
+                    errors: verification_errors,
+                }
+                .into())
+            } else {
+                Ok(())
+            }
+        }
+        SubCommands::Apply { plan, dry_run } => {
+            let report = apply::apply(&registry, &plan, dry_run).await?;
+
+            match args.output {
+                Output::Text => report.print(),
+                Output::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+
+            if report.is_success() {
+                Ok(())
+            } else {
+                Err(Error::Push {
+                    image: plan.source.to_string(),
+                    failed_tags: report.failed_tags(),
+                }
+                .into())
+            }
         }
     }
 }
@@ -222,6 +1082,7 @@ mod tests {
             version_to_tag(
                 &Reference::from_str("hello-world:16.0.0").unwrap(),
                 Some(Version::from_str("1.2.3").unwrap()),
+                &None,
                 &None
             )
             .unwrap(),
@@ -235,6 +1096,7 @@ mod tests {
             version_to_tag(
                 &Reference::from_str("hello-world:16.0.0").unwrap(),
                 None,
+                &None,
                 &None
             )
             .unwrap(),
@@ -248,7 +1110,8 @@ mod tests {
             version_to_tag(
                 &Reference::from_str("hello-world:v16.0.0").unwrap(),
                 None,
-                &Some(String::from("v"))
+                &Some(String::from("v")),
+                &None
             )
             .unwrap(),
             Version::from_str("16.0.0").unwrap()
@@ -261,18 +1124,50 @@ mod tests {
             &Reference::from_str("hello-world:latest").unwrap(),
             Some(Version::from_str("0.8.1+zstd.1.5.0").unwrap()),
             &None,
+            &None,
         )
         .unwrap_err();
 
         assert_eq!(err.to_string(), "0.8.1+zstd.1.5.0 contains build metadata which contains characters that are incompatible with distribution spec: https://github.com/opencontainers/distribution-spec/issues/154")
     }
 
+    #[test]
+    fn parse_version_from_image_tag_with_suffix() {
+        assert_eq!(
+            version_to_tag(
+                &Reference::from_str("hello-world:v16.0.0-rc.1-alpine").unwrap(),
+                None,
+                &Some(String::from("v")),
+                &Some(String::from("-alpine"))
+            )
+            .unwrap(),
+            Version::from_str("16.0.0-rc.1").unwrap()
+        )
+    }
+
+    #[test]
+    fn fail_on_none_matching_version_suffix() {
+        let err = version_to_tag(
+            &Reference::from_str("hello-world:1.2.3-slim").unwrap(),
+            None,
+            &None,
+            &Some(String::from("-alpine")),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "The image tag 1.2.3-slim doesn't end with the suffix -alpine"
+        )
+    }
+
     #[test]
     fn fail_on_none_matching_version_prefix() {
         let err = version_to_tag(
             &Reference::from_str("hello-world:1.2.3").unwrap(),
             None,
             &Some(String::from("v")),
+            &None,
         )
         .unwrap_err();
 
@@ -282,9 +1177,266 @@ mod tests {
         )
     }
 
+    mod password {
+        use super::*;
+
+        #[test]
+        fn read_secret_trims_trailing_newline() {
+            assert_eq!(
+                read_secret("s3cr3t\n".as_bytes(), "password").unwrap(),
+                "s3cr3t"
+            );
+            assert_eq!(
+                read_secret("s3cr3t\r\n".as_bytes(), "password").unwrap(),
+                "s3cr3t"
+            );
+        }
+
+        #[test]
+        fn read_secret_keeps_inner_whitespace() {
+            assert_eq!(
+                read_secret(" s3 cr3t \n".as_bytes(), "password").unwrap(),
+                " s3 cr3t "
+            );
+        }
+
+        #[test]
+        fn fail_on_empty_password() {
+            let err = read_secret("\n".as_bytes(), "password").unwrap_err();
+
+            assert_eq!(err.to_string(), "The password provided via stdin is empty.");
+        }
+
+        #[test]
+        fn fail_on_user_without_password() {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "--user",
+                "foo",
+                "tag",
+                "localhost:5135/postgres:15.8.0",
+            ])
+            .unwrap();
+
+            assert_eq!(
+                args.registry_auth("localhost:5135")
+                    .unwrap_err()
+                    .to_string(),
+                "The user foo requires a password provided by --password-stdin or --password-env."
+            );
+        }
+
+        #[test]
+        fn fail_on_password_stdin_without_user() {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "--password-stdin",
+                "tag",
+                "localhost:5135/postgres:15.8.0",
+            ])
+            .unwrap();
+
+            assert_eq!(
+                args.registry_auth("localhost:5135")
+                    .unwrap_err()
+                    .to_string(),
+                "--password-stdin requires --user."
+            );
+        }
+
+        #[test]
+        fn fail_on_password_env_without_user() {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "--password-env",
+                "PASSWORD",
+                "tag",
+                "localhost:5135/postgres:15.8.0",
+            ])
+            .unwrap();
+
+            assert_eq!(
+                args.registry_auth("localhost:5135")
+                    .unwrap_err()
+                    .to_string(),
+                "--password-env requires --user."
+            );
+        }
+    }
+
+    mod token {
+        use super::*;
+
+        #[test]
+        fn bearer_token_from_env() {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "--token-env",
+                "OCI_SEMVER_TAGGING_TEST_TOKEN",
+                "tag",
+                "localhost:5135/postgres:15.8.0",
+            ])
+            .unwrap();
+
+            assert_eq!(
+                args.registry_auth_with_env("localhost:5135", |name| {
+                    assert_eq!(name, "OCI_SEMVER_TAGGING_TEST_TOKEN");
+                    Ok(String::from("t0k3n"))
+                })
+                .unwrap(),
+                RegistryAuth::Bearer(String::from("t0k3n"))
+            );
+        }
+
+        #[test]
+        fn fail_on_token_with_user() {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "--user",
+                "foo",
+                "--token-stdin",
+                "tag",
+                "localhost:5135/postgres:15.8.0",
+            ])
+            .unwrap();
+
+            assert_eq!(
+                args.registry_auth("localhost:5135")
+                    .unwrap_err()
+                    .to_string(),
+                "A token cannot be combined with --user, --password-stdin or --password-env."
+            );
+        }
+    }
+
+    mod other_registry {
+        use super::*;
+
+        #[test]
+        fn explicit_credentials_apply_to_other_registries() {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "--user",
+                "foo",
+                "--password-env",
+                "PASSWORD",
+                "promote",
+                "localhost:5135/postgres:15.8.0",
+                "registry.example.com/postgres",
+            ])
+            .unwrap();
+            let registry_auth = RegistryAuth::Basic(String::from("foo"), String::from("bar"));
+
+            assert_eq!(
+                args.other_registry_auth(&registry_auth, "registry.example.com")
+                    .unwrap(),
+                registry_auth
+            );
+        }
+
+        #[test]
+        fn reuse_credentials_for_same_registry() {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "promote",
+                "localhost:5135/postgres:15.8.0",
+                "localhost:5135/production/postgres",
+            ])
+            .unwrap();
+            let registry_auth = RegistryAuth::Bearer(String::from("t0k3n"));
+
+            assert_eq!(
+                args.other_registry_auth(&registry_auth, "localhost:5135")
+                    .unwrap(),
+                registry_auth
+            );
+        }
+    }
+
+    mod tls {
+        use super::*;
+
+        #[test]
+        fn https_except_insecure_registries() -> Result<()> {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "--insecure-registry",
+                "localhost:5000",
+                "--insecure-registry",
+                "mirror.local",
+                "tag",
+                "localhost:5135/postgres:15.8.0",
+            ])?;
+
+            assert_eq!(
+                args.client_config()?.protocol,
+                ClientProtocol::HttpsExcept(vec![
+                    String::from("localhost:5000"),
+                    String::from("mirror.local")
+                ])
+            );
+
+            Ok(())
+        }
+
+        #[test]
+        fn split_pem_bundle() {
+            let bundle = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n\
+                -----BEGIN CERTIFICATE-----\nMIIC\n-----END CERTIFICATE-----\n";
+
+            let certificates = certificates(bundle.as_bytes().to_vec());
+
+            assert_eq!(
+                certificates
+                    .iter()
+                    .map(|c| String::from_utf8(c.data.clone()).unwrap())
+                    .collect::<Vec<_>>(),
+                vec![
+                    String::from("-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----"),
+                    String::from("-----BEGIN CERTIFICATE-----\nMIIC\n-----END CERTIFICATE-----"),
+                ]
+            );
+        }
+    }
+
     mod parse_args {
         use super::*;
 
+        #[test]
+        fn tag_suffix_with_leading_hyphen() -> Result<()> {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "validate",
+                "--tag-suffix",
+                "-alpine",
+                "localhost:5135/postgres",
+            ])?;
+
+            assert!(matches!(
+                args.sub_command,
+                SubCommands::Validate { tag_suffix: Some(suffix), .. } if suffix == "-alpine"
+            ));
+
+            Ok(())
+        }
+
+        #[test]
+        fn latest_with_default_tag_name() -> Result<()> {
+            let args = Args::try_parse_from([
+                "oci-semver-tagging",
+                "tag",
+                "--latest",
+                "localhost:5135/postgres:15.8.0",
+            ])?;
+
+            assert!(matches!(
+                args.sub_command,
+                SubCommands::Tag { latest: Some(latest), .. } if latest == "latest"
+            ));
+
+            Ok(())
+        }
+
         #[test]
         fn without_auth() -> Result<()> {
             let args = Args::try_parse_from([
@@ -301,11 +1453,25 @@ mod tests {
                         stdin: false,
                         env: None
                     },
+                    token: Token {
+                        stdin: false,
+                        env: None
+                    },
                     protocol: Protocol::Https,
+                    insecure_registries: Vec::new(),
+                    ca_files: Vec::new(),
+                    output: Output::Text,
                     sub_command: SubCommands::Tag {
                         image: Reference::from_str("localhost:5135/postgres:15.8.0")?,
                         tag_version: None,
                         tag_prefix: None,
+                        tag_suffix: None,
+                        latest: None,
+                        pre_release_channels: false,
+                        require_moving_tags: false,
+                        yanked: Vec::new(),
+                        plan_out: None,
+                        no_verify: false,
                         dry_run: false
                     }
                 }
@@ -1,10 +1,16 @@
-use anyhow::Result;
 use clap::Parser;
 use oci_semver_tagging::{run, Args};
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = Args::parse();
 
-    run(args).await
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::from(err.exit_code())
+        }
+    }
 }
@@ -96,6 +96,17 @@ impl PartialSemverVersion {
         self.full().unwrap()
     }
 
+    /// The pre-release channel, i.e. the first identifier of the pre-release. For example, `rc`
+    /// for the full version `2.0.0-rc.1` as well as for the channel tags `2.0-rc` and `2-rc`.
+    pub fn channel(&self) -> Option<&str> {
+        let pre = match self {
+            Self::Major(comparator) | Self::MajorMinor(comparator) => &comparator.pre,
+            Self::Full(version) => &version.pre,
+        };
+
+        pre.as_str().split('.').next().filter(|c| !c.is_empty())
+    }
+
     fn to_version(&self) -> Version {
         match self {
             Self::Major(comparator) => Version {
@@ -154,17 +165,40 @@ impl FromStr for PartialSemverVersion {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match Version::from_str(s).map_err(|e| e.to_string()) {
             Ok(version) => Ok(Self::Full(version)),
-            Err(err) => match Comparator::from_str(&format!("={s}")) {
-                Ok(comparator) => {
-                    assert!(comparator.patch.is_none(), "Patch must be none");
-                    if comparator.minor.is_some() {
-                        Ok(Self::MajorMinor(comparator))
-                    } else {
-                        Ok(Self::Major(comparator))
+            Err(err) => {
+                let (partial, channel) = match s.split_once('-') {
+                    Some((partial, channel)) => (partial, Some(channel)),
+                    None => (s, None),
+                };
+
+                match Comparator::from_str(&format!("={partial}")) {
+                    Ok(mut comparator) => {
+                        if comparator.patch.is_some() {
+                            return Err(format!("Cannot parse {s} as full semver version ({err}) nor as partial semver version because {partial} contains a patch version"));
+                        }
+                        if let Some(channel) = channel {
+                            if channel.is_empty() {
+                                return Err(format!(
+                                    "Cannot parse {s} as channel tag because the channel is empty"
+                                ));
+                            }
+                            if channel.contains('.') {
+                                return Err(format!("Cannot parse {s} as channel tag because the channel {channel} consists of more than one pre-release identifier"));
+                            }
+                            comparator.pre = Prerelease::new(channel).map_err(|e| {
+                                format!("Cannot parse {s} as channel tag ({e})")
+                            })?;
+                        }
+
+                        if comparator.minor.is_some() {
+                            Ok(Self::MajorMinor(comparator))
+                        } else {
+                            Ok(Self::Major(comparator))
+                        }
                     }
+                    Err(err2) => Err(format!("Cannot parse {s} as full semver version ({err}) nor as partial semver version ({err2})")),
                 }
-                Err(err2) => Err(format!("Cannot parse {s} as full semver version ({err}) nor as partial semver version ({err2})")),
-            },
+            }
         }
     }
 }
@@ -172,14 +206,19 @@ impl FromStr for PartialSemverVersion {
 impl Display for PartialSemverVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            PartialSemverVersion::Major(comparator) => write!(f, "{}", comparator.major),
+            PartialSemverVersion::Major(comparator) => write!(f, "{}", comparator.major)?,
             PartialSemverVersion::MajorMinor(comparator) => write!(
                 f,
                 "{}.{}",
                 comparator.major,
                 comparator.minor.expect("Must be set in this case")
-            ),
-            PartialSemverVersion::Full(version) => write!(f, "{version}"),
+            )?,
+            PartialSemverVersion::Full(version) => return write!(f, "{version}"),
+        }
+
+        match self.channel() {
+            Some(channel) => write!(f, "-{channel}"),
+            None => Ok(()),
         }
     }
 }
@@ -249,5 +288,76 @@ mod tests {
         assert_eq!(psv.to_string().as_str(), "1.0");
         let psv = PartialSemverVersion::from_str("1.0.0").unwrap();
         assert_eq!(psv.to_string().as_str(), "1.0.0");
+        let psv = PartialSemverVersion::from_str("1-rc").unwrap();
+        assert_eq!(psv.to_string().as_str(), "1-rc");
+        let psv = PartialSemverVersion::from_str("1.0-rc").unwrap();
+        assert_eq!(psv.to_string().as_str(), "1.0-rc");
+    }
+
+    #[test]
+    fn parse_major_minor_channel() {
+        let psv = PartialSemverVersion::from_str("2.0-rc").unwrap();
+
+        assert_eq!(
+            psv,
+            PartialSemverVersion::MajorMinor(Comparator {
+                op: semver::Op::Exact,
+                major: 2,
+                minor: Some(0),
+                patch: None,
+                pre: Prerelease::new("rc").unwrap()
+            })
+        );
+        assert_eq!(psv.channel(), Some("rc"));
+    }
+
+    #[test]
+    fn fail_on_channel_with_multiple_identifiers() {
+        let psv = PartialSemverVersion::from_str("2.0-rc.1").unwrap_err();
+
+        assert_eq!(psv, String::from("Cannot parse 2.0-rc.1 as channel tag because the channel rc.1 consists of more than one pre-release identifier"));
+    }
+
+    #[test]
+    fn fail_on_full_version_with_invalid_pre_release() {
+        for tag in ["1.2.3-", "1.2.3-foo_bar", "16.8.0-bookworm_slim"] {
+            let err = PartialSemverVersion::from_str(tag).unwrap_err();
+
+            assert!(
+                err.ends_with("contains a patch version"),
+                "unexpected error for {tag}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn fail_on_empty_channel() {
+        let psv = PartialSemverVersion::from_str("1-").unwrap_err();
+        assert_eq!(
+            psv,
+            String::from("Cannot parse 1- as channel tag because the channel is empty")
+        );
+
+        let psv = PartialSemverVersion::from_str("1.2-").unwrap_err();
+        assert_eq!(
+            psv,
+            String::from("Cannot parse 1.2- as channel tag because the channel is empty")
+        );
+    }
+
+    #[test]
+    fn order_channel_before_stable() {
+        let mut versions = [
+            PartialSemverVersion::from_str("2.0").unwrap(),
+            PartialSemverVersion::from_str("2.0-rc").unwrap(),
+            PartialSemverVersion::from_str("1.9").unwrap(),
+            PartialSemverVersion::from_str("2.0-beta").unwrap(),
+        ];
+        versions.sort();
+
+        assert_eq!(
+            versions.map(|psv| psv.to_string()),
+            ["1.9", "2.0-beta", "2.0-rc", "2.0"].map(String::from)
+        );
     }
 }
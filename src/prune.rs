@@ -0,0 +1,321 @@
+use crate::PartialSemverVersion;
+use semver::Version;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How many versions the prune command keeps, counted from the highest version downwards. If a
+/// limit is not set, all versions of that level are kept.
+#[derive(Debug, Default)]
+pub struct RetentionPolicy {
+    pub majors: Option<usize>,
+    pub minors_per_major: Option<usize>,
+    pub patches_per_minor: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PruneEntry {
+    pub version: Version,
+    pub digest: String,
+    pub decision: PruneDecision,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PruneDecision {
+    Keep,
+    /// The version falls outside of the retention policy but its manifest is still referenced by
+    /// surviving tags.
+    KeepReferenced {
+        tags: Vec<String>,
+    },
+    Delete,
+}
+
+/// Decides for each stable full version whether it is kept or deleted according to the policy.
+/// The partial tags of major and major.minor lines without any kept version are pruned together
+/// with the versions of the line. Because deleting a manifest by digest removes every tag that
+/// points to it, a version is kept if any surviving tag, i.e. a partial tag of a line with kept
+/// versions, a kept full version, a pre-release or one of the `other_tags` that the policy doesn't
+/// consider such as `latest`, points to the same manifest. Pre-releases are never pruned.
+pub fn prune_plan(
+    digests: &BTreeMap<PartialSemverVersion, String>,
+    other_tags: &BTreeMap<String, String>,
+    policy: &RetentionPolicy,
+) -> Vec<PruneEntry> {
+    let versions = digests
+        .keys()
+        .filter_map(|psv| psv.full())
+        .filter(|version| version.pre.is_empty())
+        .collect::<BTreeSet<_>>();
+
+    let kept_majors = versions
+        .iter()
+        .map(|version| version.major)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .rev()
+        .take(policy.majors.unwrap_or(usize::MAX))
+        .collect::<BTreeSet<_>>();
+
+    let mut kept_minors = BTreeSet::new();
+    for major in &kept_majors {
+        kept_minors.extend(
+            versions
+                .iter()
+                .filter(|version| version.major == *major)
+                .map(|version| (version.major, version.minor))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .rev()
+                .take(policy.minors_per_major.unwrap_or(usize::MAX)),
+        );
+    }
+
+    let mut kept_versions = BTreeSet::<&Version>::new();
+    for (major, minor) in &kept_minors {
+        kept_versions.extend(
+            versions
+                .iter()
+                .filter(|version| version.major == *major && version.minor == *minor)
+                .rev()
+                .take(policy.patches_per_minor.unwrap_or(usize::MAX)),
+        );
+    }
+
+    let surviving_tags = digests
+        .iter()
+        .filter(|(psv, _)| match psv {
+            PartialSemverVersion::Full(version) => {
+                !version.pre.is_empty() || kept_versions.contains(version)
+            }
+            PartialSemverVersion::Major(comparator)
+            | PartialSemverVersion::MajorMinor(comparator) => {
+                // Channel tags belong to pre-releases which are never pruned.
+                !comparator.pre.is_empty()
+                    || kept_versions.iter().any(|version| {
+                        version.major == comparator.major
+                            && comparator.minor.is_none_or(|minor| version.minor == minor)
+                    })
+            }
+        })
+        .map(|(psv, digest)| (psv.to_string(), digest))
+        .chain(other_tags.iter().map(|(tag, digest)| (tag.clone(), digest)))
+        .collect::<Vec<_>>();
+
+    versions
+        .into_iter()
+        .rev()
+        .map(|version| {
+            let digest = digests
+                .get(&PartialSemverVersion::from(version.clone()))
+                .expect("Digest must be present for every tag");
+
+            let decision = if kept_versions.contains(version) {
+                PruneDecision::Keep
+            } else {
+                let tags = surviving_tags
+                    .iter()
+                    .filter(|(_, d)| *d == digest)
+                    .map(|(tag, _)| tag.clone())
+                    .collect::<Vec<_>>();
+                if tags.is_empty() {
+                    PruneDecision::Delete
+                } else {
+                    PruneDecision::KeepReferenced { tags }
+                }
+            };
+
+            PruneEntry {
+                version: version.clone(),
+                digest: digest.clone(),
+                decision,
+            }
+        })
+        .collect()
+}
+
+/// Prints the plan as table with the decision for each version.
+pub fn print_plan(plan: &[PruneEntry], dry_run: bool) {
+    println!("{:<20} {:<20} {:<10} REASON", "VERSION", "DIGEST", "ACTION");
+    for entry in plan {
+        let (action, reason) = match &entry.decision {
+            PruneDecision::Keep => ("keep", String::from("retention policy")),
+            PruneDecision::KeepReferenced { tags } => {
+                ("keep", format!("referenced by {}", tags.join(", ")))
+            }
+            PruneDecision::Delete if dry_run => ("delete", String::from("dry run")),
+            PruneDecision::Delete => ("delete", String::new()),
+        };
+        println!(
+            "{:<20} {:<20} {:<10} {reason}",
+            entry.version.to_string(),
+            short_digest(&entry.digest),
+            action
+        );
+    }
+}
+
+fn short_digest(digest: &str) -> &str {
+    &digest[..digest.len().min("sha256:".len() + 12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn digests(tags: &[(&str, &str)]) -> BTreeMap<PartialSemverVersion, String> {
+        tags.iter()
+            .map(|(tag, digest)| {
+                (
+                    PartialSemverVersion::from_str(tag).unwrap(),
+                    format!("sha256:{digest}"),
+                )
+            })
+            .collect()
+    }
+
+    fn decisions(plan: Vec<PruneEntry>) -> Vec<(String, PruneDecision)> {
+        plan.into_iter()
+            .map(|entry| (entry.version.to_string(), entry.decision))
+            .collect()
+    }
+
+    #[test]
+    fn keep_last_majors_minors_and_patches() {
+        let digests = digests(&[
+            ("1.0.0", "100"),
+            ("2.0.0", "200"),
+            ("2.1.0", "210"),
+            ("2.1.1", "211"),
+            ("3.0.0", "300"),
+            ("3.1.0", "310"),
+            ("3.1.1", "311"),
+            ("3.1.2", "312"),
+        ]);
+
+        let plan = prune_plan(
+            &digests,
+            &BTreeMap::new(),
+            &RetentionPolicy {
+                majors: Some(2),
+                minors_per_major: Some(1),
+                patches_per_minor: Some(2),
+            },
+        );
+
+        assert_eq!(
+            decisions(plan),
+            vec![
+                (String::from("3.1.2"), PruneDecision::Keep),
+                (String::from("3.1.1"), PruneDecision::Keep),
+                (String::from("3.1.0"), PruneDecision::Delete),
+                (String::from("3.0.0"), PruneDecision::Delete),
+                (String::from("2.1.1"), PruneDecision::Keep),
+                (String::from("2.1.0"), PruneDecision::Keep),
+                (String::from("2.0.0"), PruneDecision::Delete),
+                (String::from("1.0.0"), PruneDecision::Delete),
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_partial_tags_of_pruned_lines() {
+        let digests = digests(&[
+            ("1", "110"),
+            ("1.1", "110"),
+            ("1.0", "100"),
+            ("1.0.0", "100"),
+            ("1.1.0", "110"),
+            ("2", "200"),
+            ("2.0", "200"),
+            ("2.0.0", "200"),
+            ("3-rc", "300"),
+            ("3.0.0-rc.1", "300"),
+        ]);
+
+        let plan = prune_plan(
+            &digests,
+            &BTreeMap::new(),
+            &RetentionPolicy {
+                majors: Some(1),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            decisions(plan),
+            vec![
+                (String::from("2.0.0"), PruneDecision::Keep),
+                (String::from("1.1.0"), PruneDecision::Delete),
+                (String::from("1.0.0"), PruneDecision::Delete),
+            ]
+        );
+    }
+
+    #[test]
+    fn never_delete_manifests_referenced_by_partial_tags_of_kept_lines() {
+        let digests = digests(&[
+            ("2", "211"),
+            ("2.1", "210"),
+            ("2.0", "200"),
+            ("2.0.0", "200"),
+            ("2.1.0", "210"),
+            ("2.1.1", "211"),
+        ]);
+
+        let plan = prune_plan(
+            &digests,
+            &BTreeMap::new(),
+            &RetentionPolicy {
+                minors_per_major: Some(1),
+                patches_per_minor: Some(1),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            decisions(plan),
+            vec![
+                (String::from("2.1.1"), PruneDecision::Keep),
+                (
+                    String::from("2.1.0"),
+                    PruneDecision::KeepReferenced {
+                        tags: vec![String::from("2.1")]
+                    }
+                ),
+                (String::from("2.0.0"), PruneDecision::Delete),
+            ]
+        );
+    }
+
+    #[test]
+    fn never_delete_manifests_referenced_by_other_tags() {
+        let digests = digests(&[("1.0.0", "100"), ("1.1.0", "110"), ("2.0.0", "200")]);
+        let other_tags = BTreeMap::from([
+            (String::from("latest"), String::from("sha256:110")),
+            (String::from("1.1.0-alpine"), String::from("sha256:110")),
+        ]);
+
+        let plan = prune_plan(
+            &digests,
+            &other_tags,
+            &RetentionPolicy {
+                majors: Some(1),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            decisions(plan),
+            vec![
+                (String::from("2.0.0"), PruneDecision::Keep),
+                (
+                    String::from("1.1.0"),
+                    PruneDecision::KeepReferenced {
+                        tags: vec![String::from("1.1.0-alpine"), String::from("latest")]
+                    }
+                ),
+                (String::from("1.0.0"), PruneDecision::Delete),
+            ]
+        );
+    }
+}
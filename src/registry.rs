@@ -0,0 +1,1179 @@
+use crate::{
+    copy::{self, RawManifest},
+    credentials::IDENTITY_TOKEN_USER,
+    validate::is_manifest_unknown,
+};
+use anyhow::{anyhow, Context, Result};
+use oci_client::{
+    client::{CertificateEncoding, ClientConfig, ClientProtocol},
+    manifest::OciManifest,
+    secrets::RegistryAuth,
+    Client, Reference,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+/// The operations on a registry that tagging, validating and pruning need. The tags and manifests
+/// are addressed by references whose tag or digest selects the manifest in the repository.
+pub trait Registry: Clone + Send + Sync + 'static {
+    /// All tags of the repository of `image`.
+    fn list_tags(&self, image: &Reference) -> impl Future<Output = Result<Vec<String>>> + Send;
+
+    /// The digest of the manifest that `image` points to or none if there is no such manifest.
+    fn resolve_digest(
+        &self,
+        image: &Reference,
+    ) -> impl Future<Output = Result<Option<String>>> + Send;
+
+    fn pull_manifest(&self, image: &Reference) -> impl Future<Output = Result<RawManifest>> + Send;
+
+    /// Pushes the manifest to `image`.
+    fn push_manifest(
+        &self,
+        image: &Reference,
+        manifest: &RawManifest,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Deletes the tag or, if `image` references a digest, the manifest.
+    fn delete(&self, image: &Reference) -> impl Future<Output = Result<()>> + Send;
+
+    /// Ensures that everything `manifest` references exists in the repository of `target` so that
+    /// `manifest` can be pushed there although it has been pulled from `source` of the registry
+    /// `from`.
+    fn copy_references(
+        &self,
+        from: &Self,
+        source: &Reference,
+        target: &Reference,
+        manifest: &RawManifest,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// The registry accessed with the oci-client.
+#[derive(Clone)]
+pub struct OciRegistry {
+    client: Client,
+    registry_auth: RegistryAuth,
+    /// Lists tags and deletes with the same protocol and CA certificates as the client.
+    raw: Arc<RawRegistryClient>,
+}
+
+impl OciRegistry {
+    pub fn from_config(config: ClientConfig, registry_auth: RegistryAuth) -> Result<Self> {
+        let raw = RawRegistryClient::new(&config)?;
+        let client = Client::try_from(config)
+            .context("Cannot create registry client from the given TLS configuration.")?;
+
+        Ok(Self {
+            client,
+            registry_auth,
+            raw: Arc::new(raw),
+        })
+    }
+
+    /// Exchanges an identity token from Docker's or Podman's config files for an access token that
+    /// grants access to the given repositories of the registry. Other credentials are kept.
+    pub(crate) async fn exchange_identity_token(self, repositories: &[&Reference]) -> Result<Self> {
+        match &self.registry_auth {
+            RegistryAuth::Basic(user, identity_token) if user == IDENTITY_TOKEN_USER => {
+                let access_token = self
+                    .raw
+                    .exchange_identity_token(identity_token, repositories)
+                    .await?;
+                Ok(self.with_auth(RegistryAuth::Bearer(access_token)))
+            }
+            _ => Ok(self),
+        }
+    }
+
+    /// The same registry client with other credentials, e.g. for another registry.
+    pub fn with_auth(&self, registry_auth: RegistryAuth) -> Self {
+        Self {
+            registry_auth,
+            ..self.clone()
+        }
+    }
+}
+
+impl Registry for OciRegistry {
+    async fn list_tags(&self, image: &Reference) -> Result<Vec<String>> {
+        list_all_tags(|page| self.raw.tags_page(&self.registry_auth, image, page)).await
+    }
+
+    async fn resolve_digest(&self, image: &Reference) -> Result<Option<String>> {
+        match self
+            .client
+            .fetch_manifest_digest(image, &self.registry_auth)
+            .await
+        {
+            Ok(digest) => Ok(Some(digest)),
+            Err(err) if is_manifest_unknown(&err) => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("Cannot fetch manifest of {image}")),
+        }
+    }
+
+    async fn pull_manifest(&self, image: &Reference) -> Result<RawManifest> {
+        copy::pull_raw_manifest(&self.client, &self.registry_auth, image).await
+    }
+
+    async fn push_manifest(&self, image: &Reference, manifest: &RawManifest) -> Result<()> {
+        // Pushing doesn't authenticate on its own if nothing has been pulled from the registry yet.
+        self.client
+            .store_auth_if_needed(image.resolve_registry(), &self.registry_auth)
+            .await;
+        copy::push_raw_manifest(&self.client, image, manifest).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, image: &Reference) -> Result<()> {
+        let reference = image
+            .digest()
+            .or(image.tag())
+            .ok_or_else(|| anyhow!("{image} references neither a tag nor a digest"))?;
+
+        self.raw.delete(&self.registry_auth, image, reference).await
+    }
+
+    async fn copy_references(
+        &self,
+        from: &Self,
+        source: &Reference,
+        target: &Reference,
+        manifest: &RawManifest,
+    ) -> Result<()> {
+        copy::copy_references(
+            &self.client,
+            &from.registry_auth,
+            source,
+            &self.registry_auth,
+            target,
+            &manifest.manifest,
+        )
+        .await
+    }
+}
+
+/// A registry that only lives in memory for testing the workflows without a real registry. Blobs
+/// are not stored, thus only manifests and tags are copied between repositories.
+#[derive(Clone, Default)]
+pub struct InMemoryRegistry {
+    state: Arc<Mutex<InMemoryState>>,
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    repositories: HashMap<String, InMemoryRepository>,
+    failing_pushes: HashSet<String>,
+}
+
+#[derive(Default)]
+struct InMemoryRepository {
+    tags: BTreeMap<String, String>,
+    manifests: HashMap<String, RawManifest>,
+}
+
+impl InMemoryRegistry {
+    /// Stores the manifest under the tag or digest of `image`.
+    pub fn insert(&self, image: &Reference, manifest: RawManifest) {
+        self.store(&repository_key(image), image.tag(), manifest);
+    }
+
+    fn store(&self, repository: &str, tag: Option<&str>, manifest: RawManifest) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Registry state must not be poisoned");
+        let repository = state
+            .repositories
+            .entry(repository.to_string())
+            .or_default();
+        if let Some(tag) = tag {
+            repository
+                .tags
+                .insert(tag.to_string(), manifest.digest.clone());
+        }
+        repository
+            .manifests
+            .insert(manifest.digest.clone(), manifest);
+    }
+
+    /// Lets all following pushes to `image` fail, e.g. to simulate partial pushes.
+    pub fn fail_pushes_to(&self, image: &Reference) {
+        self.state
+            .lock()
+            .expect("Registry state must not be poisoned")
+            .failing_pushes
+            .insert(image.whole());
+    }
+
+    fn resolve(&self, image: &Reference) -> Option<String> {
+        let state = self
+            .state
+            .lock()
+            .expect("Registry state must not be poisoned");
+        let repository = state.repositories.get(&repository_key(image))?;
+        match image.digest() {
+            Some(digest) => repository
+                .manifests
+                .contains_key(digest)
+                .then(|| digest.to_string()),
+            None => repository.tags.get(image.tag()?).cloned(),
+        }
+    }
+}
+
+impl Registry for InMemoryRegistry {
+    async fn list_tags(&self, image: &Reference) -> Result<Vec<String>> {
+        let state = self
+            .state
+            .lock()
+            .expect("Registry state must not be poisoned");
+        Ok(state
+            .repositories
+            .get(&repository_key(image))
+            .map(|repository| repository.tags.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn resolve_digest(&self, image: &Reference) -> Result<Option<String>> {
+        Ok(self.resolve(image))
+    }
+
+    async fn pull_manifest(&self, image: &Reference) -> Result<RawManifest> {
+        let digest = self
+            .resolve(image)
+            .ok_or_else(|| anyhow!("Cannot pull manifest for {image}: manifest unknown"))?;
+
+        let state = self
+            .state
+            .lock()
+            .expect("Registry state must not be poisoned");
+        Ok(state.repositories[&repository_key(image)].manifests[&digest].clone())
+    }
+
+    async fn push_manifest(&self, image: &Reference, manifest: &RawManifest) -> Result<()> {
+        if self
+            .state
+            .lock()
+            .expect("Registry state must not be poisoned")
+            .failing_pushes
+            .contains(&image.whole())
+        {
+            return Err(anyhow!("Cannot push manifest to {image}"));
+        }
+
+        self.insert(image, manifest.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, image: &Reference) -> Result<()> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Registry state must not be poisoned");
+        let repository = state
+            .repositories
+            .get_mut(&repository_key(image))
+            .ok_or_else(|| anyhow!("Cannot delete {image}: repository unknown"))?;
+
+        match (image.digest(), image.tag()) {
+            (Some(digest), _) => {
+                repository.manifests.remove(digest);
+                repository.tags.retain(|_, d| d != digest);
+            }
+            (None, Some(tag)) => {
+                repository.tags.remove(tag);
+            }
+            (None, None) => return Err(anyhow!("Cannot delete {image}: no tag or digest")),
+        }
+        Ok(())
+    }
+
+    async fn copy_references(
+        &self,
+        from: &Self,
+        source: &Reference,
+        target: &Reference,
+        manifest: &RawManifest,
+    ) -> Result<()> {
+        let OciManifest::ImageIndex(index) = &manifest.manifest else {
+            return Ok(());
+        };
+
+        let children = {
+            let state = from
+                .state
+                .lock()
+                .expect("Registry state must not be poisoned");
+            let repository = state
+                .repositories
+                .get(&repository_key(source))
+                .ok_or_else(|| anyhow!("Cannot copy from {source}: repository unknown"))?;
+            index
+                .manifests
+                .iter()
+                .map(|entry| {
+                    repository
+                        .manifests
+                        .get(&entry.digest)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("Cannot pull manifest {} of {source}", entry.digest))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        for child in children {
+            self.store(&repository_key(target), None, child);
+        }
+        Ok(())
+    }
+}
+
+/// Sends the requests that the oci-client doesn't support, i.e. deleting manifests by digest and
+/// reading the `Link` header of the tag list. The requests are sent with the same protocol and CA
+/// certificates as the registry client and, if the registry asks for it, with a token for the
+/// requested scope.
+struct RawRegistryClient {
+    http: reqwest::Client,
+    protocol: ClientProtocol,
+}
+
+impl RawRegistryClient {
+    fn new(config: &ClientConfig) -> Result<Self> {
+        let mut builder = reqwest::Client::builder();
+        for certificate in &config.extra_root_certificates {
+            let certificate = match certificate.encoding {
+                CertificateEncoding::Der => reqwest::Certificate::from_der(&certificate.data),
+                CertificateEncoding::Pem => reqwest::Certificate::from_pem(&certificate.data),
+            }
+            .context("Cannot use CA certificate for requests to the registry.")?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        Ok(Self {
+            http: builder.build()?,
+            protocol: config.protocol.clone(),
+        })
+    }
+
+    fn scheme_for(&self, registry: &str) -> &'static str {
+        match &self.protocol {
+            ClientProtocol::Http => "http",
+            ClientProtocol::HttpsExcept(exceptions) if exceptions.iter().any(|e| e == registry) => {
+                "http"
+            }
+            ClientProtocol::Https | ClientProtocol::HttpsExcept(_) => "https",
+        }
+    }
+
+    fn base_url(&self, image: &Reference) -> String {
+        let registry = image.resolve_registry();
+        format!("{}://{registry}", self.scheme_for(registry))
+    }
+
+    /// Sends the request and repeats it with a token for the `action` on the repository of `image`
+    /// if the registry asks for one.
+    async fn send(
+        &self,
+        registry_auth: &RegistryAuth,
+        image: &Reference,
+        action: &str,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let response = with_auth(request(), registry_auth).send().await?;
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+            return refused(response, image, action);
+        }
+
+        let Some(challenge) = response
+            .headers()
+            .get(reqwest::header::WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .and_then(BearerChallenge::parse)
+        else {
+            return refused(response, image, action);
+        };
+
+        let token = self
+            .token(registry_auth, &challenge, image.repository(), action)
+            .await?;
+        refused(request().bearer_auth(token).send().await?, image, action)
+    }
+
+    async fn delete(
+        &self,
+        registry_auth: &RegistryAuth,
+        image: &Reference,
+        digest: &str,
+    ) -> Result<()> {
+        let url = format!(
+            "{}/v2/{}/manifests/{digest}",
+            self.base_url(image),
+            image.repository()
+        );
+
+        let response = self
+            .send(registry_auth, image, "delete", || self.http.delete(&url))
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "The registry responded with {status} when deleting {url}: {}",
+                response.text().await.unwrap_or_default()
+            ))
+        }
+    }
+
+    /// Fetches one page of the tag list, either the first one, the one after the last tag of the
+    /// previous page or the one that the `Link` header of the previous page points to.
+    async fn tags_page(
+        &self,
+        registry_auth: &RegistryAuth,
+        image: &Reference,
+        page: Option<NextTagPage>,
+    ) -> Result<TagPage> {
+        #[derive(serde::Deserialize)]
+        struct TagList {
+            tags: Option<Vec<String>>,
+        }
+
+        let base_url = reqwest::Url::parse(&self.base_url(image))?;
+        let tags_url = base_url.join(&format!("/v2/{}/tags/list", image.repository()))?;
+        let page_size = TAG_PAGE_SIZE.to_string();
+        let (url, query) = match page {
+            None => (tags_url, vec![("n", page_size)]),
+            Some(NextTagPage::After(last)) => (tags_url, vec![("n", page_size), ("last", last)]),
+            Some(NextTagPage::Link(link)) => {
+                let url = base_url
+                    .join(&link)
+                    .with_context(|| format!("Cannot follow the link to {link}"))?;
+                // The credentials must not be sent to another host or via another scheme.
+                if url.origin() != base_url.origin() {
+                    return Err(anyhow!(
+                        "The registry links the next page of tags to {url} which is not on {base_url}."
+                    ));
+                }
+                (url, Vec::new())
+            }
+        };
+
+        let response = self
+            .send(registry_auth, image, "pull", || {
+                self.http.get(url.clone()).query(&query)
+            })
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot fetch tags from {url}"))?;
+
+        let next = response
+            .headers()
+            .get(reqwest::header::LINK)
+            .and_then(|link| link.to_str().ok())
+            .and_then(next_link);
+        let tags = response.json::<TagList>().await?.tags.unwrap_or_default();
+
+        Ok(TagPage { tags, next })
+    }
+
+    /// Exchanges the identity token, an OAuth2 refresh token, at the token service that the
+    /// registry names in its challenge for an access token with pull, push and delete scopes on
+    /// the repositories. The token service grants only the actions that the user is allowed to.
+    async fn exchange_identity_token(
+        &self,
+        identity_token: &str,
+        repositories: &[&Reference],
+    ) -> Result<String> {
+        let image = repositories
+            .first()
+            .ok_or_else(|| anyhow!("An identity token can only be exchanged for repositories."))?;
+        let registry = image.resolve_registry();
+
+        let response = self
+            .http
+            .get(format!("{}/v2/", self.base_url(image)))
+            .send()
+            .await?;
+        let challenge = response
+            .headers()
+            .get(reqwest::header::WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .and_then(BearerChallenge::parse)
+            .ok_or_else(|| {
+                anyhow!("{registry} names no token service at which its identity token could be exchanged.")
+            })?;
+
+        let scope = repositories
+            .iter()
+            .map(|image| format!("repository:{}:pull,push,delete", image.repository()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", identity_token),
+            ("client_id", env!("CARGO_PKG_NAME")),
+            ("scope", &scope),
+        ];
+        if let Some(service) = &challenge.service {
+            form.push(("service", service));
+        }
+
+        let response = self
+            .http
+            .post(&challenge.realm)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| {
+                format!(
+                    "Cannot exchange the identity token for {registry} at {}.",
+                    challenge.realm
+                )
+            })?
+            .json::<TokenResponse>()
+            .await?;
+
+        response
+            .access_token
+            .or(response.token)
+            .ok_or_else(|| anyhow!("The token response of {} has no token.", challenge.realm))
+    }
+
+    async fn token(
+        &self,
+        registry_auth: &RegistryAuth,
+        challenge: &BearerChallenge,
+        repository: &str,
+        action: &str,
+    ) -> Result<String> {
+        let scope = challenge
+            .scope
+            .clone()
+            .unwrap_or_else(|| format!("repository:{repository}:{action}"));
+        let mut query = vec![("scope", scope)];
+        if let Some(service) = &challenge.service {
+            query.push(("service", service.clone()));
+        }
+
+        let response = with_auth(self.http.get(&challenge.realm), registry_auth)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot fetch token from {}.", challenge.realm))?
+            .json::<TokenResponse>()
+            .await?;
+
+        response
+            .token
+            .or(response.access_token)
+            .ok_or_else(|| anyhow!("The token response of {} has no token.", challenge.realm))
+    }
+}
+
+/// The response of a token service, which calls the token `token` or, for OAuth2,
+/// `access_token`.
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Turns responses that refuse the credentials into errors that keep the status so that they are
+/// reported as [`Error::Authentication`](crate::Error::Authentication).
+fn refused(
+    response: reqwest::Response,
+    image: &Reference,
+    action: &str,
+) -> Result<reqwest::Response> {
+    match response.status() {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => response
+            .error_for_status()
+            .with_context(|| format!("The registry refused to {action} {}.", image.repository())),
+        _ => Ok(response),
+    }
+}
+
+/// The number of tags requested per page of the tag list.
+const TAG_PAGE_SIZE: usize = 100;
+
+/// A page of the tag list together with the target of its `Link` header if the registry sent one.
+struct TagPage {
+    tags: Vec<String>,
+    next: Option<String>,
+}
+
+/// How the page after the previous one is requested.
+enum NextTagPage {
+    /// The target of the previous page's `Link` header.
+    Link(String),
+    /// The last tag of the previous page for registries that don't send a `Link` header.
+    After(String),
+}
+
+/// Walks through all pages of the tag list. The registry's `Link` header is followed if present.
+/// Otherwise, the next page is requested with the last tag of the previous one until the registry
+/// returns an empty page. Registries may return fewer tags than requested even though further
+/// pages exist, thus the size of a page does not indicate the end of the list. If the registry
+/// returns a tag twice, it ignores `last` and the list cannot be completed.
+async fn list_all_tags<F, Fut>(mut fetch_page: F) -> Result<Vec<String>>
+where
+    F: FnMut(Option<NextTagPage>) -> Fut,
+    Fut: Future<Output = Result<TagPage>>,
+{
+    let mut tags = Vec::new();
+    let mut seen = HashSet::new();
+    let mut next = None;
+
+    loop {
+        let description = match &next {
+            Some(NextTagPage::Link(link)) => format!("Cannot fetch the page of tags at {link}"),
+            Some(NextTagPage::After(last)) => format!("Cannot fetch the page of tags after {last}"),
+            None => String::from("Cannot fetch the first page of tags"),
+        };
+        let page = fetch_page(next.take()).await.context(description)?;
+
+        for tag in &page.tags {
+            if !seen.insert(tag.clone()) {
+                return Err(anyhow!(
+                    "The registry returned the tag {tag} more than once while paginating the tag list. The list cannot be completed."
+                ));
+            }
+        }
+
+        next = match (page.next, page.tags.last()) {
+            (Some(link), _) => Some(NextTagPage::Link(link)),
+            (None, Some(last)) => Some(NextTagPage::After(last.clone())),
+            (None, None) => return Ok(tags),
+        };
+        tags.extend(page.tags);
+    }
+}
+
+/// The target of the `rel="next"` link of a `Link: </v2/…/tags/list?n=100&last=b>; rel="next"`
+/// header.
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (target, params) = link.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("rel=")
+                    .is_some_and(|rel| rel.trim_matches('"') == "next")
+            })
+            .then(|| {
+                target
+                    .trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+fn with_auth(
+    request: reqwest::RequestBuilder,
+    registry_auth: &RegistryAuth,
+) -> reqwest::RequestBuilder {
+    match registry_auth {
+        RegistryAuth::Anonymous => request,
+        RegistryAuth::Basic(user, password) => request.basic_auth(user, Some(password)),
+        RegistryAuth::Bearer(token) => request.bearer_auth(token),
+    }
+}
+
+/// The parameters of a `WWW-Authenticate: Bearer realm="…",service="…",scope="…"` header.
+#[derive(Debug, PartialEq)]
+struct BearerChallenge {
+    realm: String,
+    service: Option<String>,
+    scope: Option<String>,
+}
+
+impl BearerChallenge {
+    fn parse(header: &str) -> Option<Self> {
+        let (scheme, params) = header.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return None;
+        }
+
+        let mut realm = None;
+        let mut service = None;
+        let mut scope = None;
+
+        let mut rest = params.trim();
+        while !rest.is_empty() {
+            let (key, value) = rest.split_once('=')?;
+            let (value, remainder) = match value.strip_prefix('"') {
+                Some(quoted) => {
+                    let (value, remainder) = quoted.split_once('"')?;
+                    (value, remainder)
+                }
+                None => value.split_once(',').unwrap_or((value, "")),
+            };
+
+            match key.trim() {
+                "realm" => realm = Some(value.to_string()),
+                "service" => service = Some(value.to_string()),
+                "scope" => scope = Some(value.to_string()),
+                _ => {}
+            }
+
+            rest = remainder.trim_start_matches(',').trim();
+        }
+
+        Some(Self {
+            realm: realm?,
+            service,
+            scope,
+        })
+    }
+}
+
+fn repository_key(image: &Reference) -> String {
+    format!("{}/{}", image.resolve_registry(), image.repository())
+}
+
+/// A minimal image manifest whose content differs per digest, for testing with the
+/// [`InMemoryRegistry`].
+#[cfg(test)]
+pub(crate) fn image_manifest(digest: &str) -> RawManifest {
+    let json = serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": oci_client::manifest::OCI_IMAGE_MEDIA_TYPE,
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest,
+            "size": 7023
+        },
+        "layers": []
+    });
+
+    RawManifest {
+        bytes: serde_json::to_vec(&json).unwrap().into(),
+        media_type: String::from(oci_client::manifest::OCI_IMAGE_MEDIA_TYPE),
+        digest: digest.to_string(),
+        manifest: serde_json::from_value(json).unwrap(),
+    }
+}
+
+/// The postgres image with the given tag, for testing with the [`InMemoryRegistry`].
+#[cfg(test)]
+pub(crate) fn image(tag: &str) -> Reference {
+    Reference::try_from(format!("localhost:5000/postgres:{tag}")).unwrap()
+}
+
+/// An [`InMemoryRegistry`] in which each of the postgres `tags` points to an [`image_manifest`]
+/// with the given digest.
+#[cfg(test)]
+pub(crate) fn registry_with_tags(tags: &[(&str, &str)]) -> InMemoryRegistry {
+    let registry = InMemoryRegistry::default();
+    for (tag, digest) in tags {
+        registry.insert(&image(tag), image_manifest(digest));
+    }
+    registry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn resolve_and_delete_tags_in_memory() {
+        let registry = InMemoryRegistry::default();
+        let image = Reference::from_str("localhost:5000/postgres:16.8.0").unwrap();
+        let partial = Reference::from_str("localhost:5000/postgres:16").unwrap();
+        registry.insert(&image, image_manifest("sha256:168"));
+        registry
+            .push_manifest(&partial, &image_manifest("sha256:168"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            registry.list_tags(&image).await.unwrap(),
+            vec![String::from("16"), String::from("16.8.0")]
+        );
+        assert_eq!(
+            registry.resolve_digest(&partial).await.unwrap(),
+            Some(String::from("sha256:168"))
+        );
+
+        registry.delete(&partial).await.unwrap();
+
+        assert_eq!(registry.resolve_digest(&partial).await.unwrap(), None);
+        assert_eq!(
+            registry.pull_manifest(&image).await.unwrap().digest,
+            "sha256:168"
+        );
+    }
+
+    #[tokio::test]
+    async fn fail_pushes_in_memory() {
+        let registry = InMemoryRegistry::default();
+        let image = Reference::from_str("localhost:5000/postgres:16").unwrap();
+        registry.fail_pushes_to(&image);
+
+        assert!(registry
+            .push_manifest(&image, &image_manifest("sha256:168"))
+            .await
+            .is_err());
+        assert_eq!(registry.resolve_digest(&image).await.unwrap(), None);
+    }
+
+    #[test]
+    fn parse_bearer_challenge() {
+        assert_eq!(
+            BearerChallenge::parse(
+                r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/postgres:delete""#
+            ),
+            Some(BearerChallenge {
+                realm: String::from("https://auth.docker.io/token"),
+                service: Some(String::from("registry.docker.io")),
+                scope: Some(String::from("repository:library/postgres:delete")),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_next_link() {
+        assert_eq!(
+            next_link(r#"</v2/postgres/tags/list?n=100&last=16.8>; rel="next""#),
+            Some(String::from("/v2/postgres/tags/list?n=100&last=16.8"))
+        );
+        assert_eq!(
+            next_link(
+                r#"<https://mirror.local/v2/postgres/tags/list?last=16>; rel=prev, <https://mirror.local/v2/postgres/tags/list?last=17>; rel=next"#
+            ),
+            Some(String::from(
+                "https://mirror.local/v2/postgres/tags/list?last=17"
+            ))
+        );
+        assert_eq!(next_link(r#"</v2/postgres/tags/list>; rel="prev""#), None);
+    }
+
+    mod pagination {
+        use super::*;
+
+        fn page(tags: &[&str], next: Option<&str>) -> TagPage {
+            TagPage {
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                next: next.map(String::from),
+            }
+        }
+
+        #[tokio::test]
+        async fn follow_link_header() {
+            let tags = list_all_tags(|next| {
+                let page = match next {
+                    None => page(&["1", "1.0"], Some("/v2/postgres/tags/list?last=1.0")),
+                    Some(NextTagPage::Link(link)) if link == "/v2/postgres/tags/list?last=1.0" => {
+                        page(&["1.0.0"], None)
+                    }
+                    Some(NextTagPage::After(last)) if last == "1.0.0" => page(&[], None),
+                    _ => panic!("Unexpected page"),
+                };
+                async move { Ok(page) }
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(tags, vec!["1", "1.0", "1.0.0"]);
+        }
+
+        #[tokio::test]
+        async fn request_pages_after_last_tag_until_empty_page() {
+            let tags = list_all_tags(|next| {
+                let page = match next {
+                    None => page(&["1", "1.0"], None),
+                    Some(NextTagPage::After(last)) if last == "1.0" => page(&["1.0.0"], None),
+                    Some(NextTagPage::After(last)) if last == "1.0.0" => page(&[], None),
+                    _ => panic!("Unexpected page"),
+                };
+                async move { Ok(page) }
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(tags, vec!["1", "1.0", "1.0.0"]);
+        }
+
+        #[tokio::test]
+        async fn fail_if_registry_ignores_last() {
+            let err = list_all_tags(|_next| async move { Ok(page(&["1", "1.0"], None)) })
+                .await
+                .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                "The registry returned the tag 1 more than once while paginating the tag list. The list cannot be completed."
+            );
+        }
+
+        #[tokio::test]
+        async fn fail_if_page_cannot_be_fetched() {
+            let err = list_all_tags(|next| async move {
+                match next {
+                    None => Ok(page(&["1"], Some("/v2/postgres/tags/list?last=1"))),
+                    Some(_) => Err(anyhow!("connection reset")),
+                }
+            })
+            .await
+            .unwrap_err();
+
+            assert_eq!(
+                format!("{err:#}"),
+                "Cannot fetch the page of tags at /v2/postgres/tags/list?last=1: connection reset"
+            );
+        }
+
+        #[tokio::test]
+        async fn refuse_links_to_other_origins() {
+            let raw = RawRegistryClient::new(&ClientConfig::default()).unwrap();
+            let image = Reference::from_str("registry.example.com/postgres:16.8.0").unwrap();
+
+            for link in [
+                "https://evil.example.com/v2/postgres/tags/list?last=1",
+                "http://registry.example.com/v2/postgres/tags/list?last=1",
+            ] {
+                let err = raw
+                    .tags_page(
+                        &RegistryAuth::Basic(String::from("foo"), String::from("bar")),
+                        &image,
+                        Some(NextTagPage::Link(String::from(link))),
+                    )
+                    .await
+                    .err()
+                    .unwrap();
+
+                assert_eq!(
+                    err.to_string(),
+                    format!("The registry links the next page of tags to {link} which is not on https://registry.example.com/.")
+                );
+            }
+        }
+    }
+
+    mod authentication {
+        use super::*;
+        use tokio::{
+            io::{AsyncReadExt as _, AsyncWriteExt as _},
+            net::TcpListener,
+        };
+
+        /// Starts a registry on localhost that answers the requests one after another with the
+        /// responses that `responses` creates for the registry's address. The requests are
+        /// recorded including their bodies.
+        async fn serve(
+            responses: impl FnOnce(&str) -> Vec<String>,
+        ) -> (Reference, Arc<Mutex<Vec<String>>>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let responses = responses(&address);
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = Arc::clone(&requests);
+            tokio::spawn(async move {
+                for response in responses {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    let body_start = loop {
+                        let read = stream.read(&mut buffer).await.unwrap();
+                        request.extend_from_slice(&buffer[..read]);
+                        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break end + 4;
+                        }
+                    };
+                    let content_length = String::from_utf8_lossy(&request[..body_start])
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    while request.len() < body_start + content_length {
+                        let read = stream.read(&mut buffer).await.unwrap();
+                        request.extend_from_slice(&buffer[..read]);
+                    }
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8_lossy(&request).into_owned());
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+
+            let image = Reference::from_str(&format!("{address}/postgres:16.8.0")).unwrap();
+            (image, requests)
+        }
+
+        fn response(status: &str, headers: &str) -> String {
+            response_with_body(status, headers, "")
+        }
+
+        fn response_with_body(status: &str, headers: &str, body: &str) -> String {
+            format!(
+                "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+
+        fn http_config() -> ClientConfig {
+            ClientConfig {
+                protocol: ClientProtocol::Http,
+                ..Default::default()
+            }
+        }
+
+        async fn list_tags_error(image: &Reference) -> crate::Error {
+            let registry = OciRegistry::from_config(
+                http_config(),
+                RegistryAuth::Basic(String::from("foo"), String::from("bar")),
+            )
+            .unwrap();
+
+            crate::Error::from(registry.list_tags(image).await.unwrap_err())
+        }
+
+        #[tokio::test]
+        async fn refused_without_challenge() {
+            let (image, _) = serve(|_| vec![response("401 Unauthorized", "")]).await;
+
+            let err = list_tags_error(&image).await;
+
+            assert_eq!(err.exit_code(), 3, "{err}");
+        }
+
+        #[tokio::test]
+        async fn forbidden() {
+            let (image, _) = serve(|_| vec![response("403 Forbidden", "")]).await;
+
+            let err = list_tags_error(&image).await;
+
+            assert_eq!(err.exit_code(), 3, "{err}");
+        }
+
+        #[tokio::test]
+        async fn refused_by_token_service() {
+            let (image, _) = serve(|address| {
+                vec![
+                    response(
+                        "401 Unauthorized",
+                        &format!(
+                            "WWW-Authenticate: Bearer realm=\"http://{address}/token\",service=\"registry\"\r\n"
+                        ),
+                    ),
+                    response("401 Unauthorized", ""),
+                ]
+            })
+            .await;
+
+            let err = list_tags_error(&image).await;
+
+            assert_eq!(err.exit_code(), 3, "{err}");
+        }
+
+        #[tokio::test]
+        async fn unreachable() {
+            let (image, _) = serve(|_| vec![response("503 Service Unavailable", "")]).await;
+
+            let err = list_tags_error(&image).await;
+
+            assert_eq!(err.exit_code(), 4, "{err}");
+        }
+
+        fn challenge(address: &str) -> String {
+            response(
+                "401 Unauthorized",
+                &format!(
+                    "WWW-Authenticate: Bearer realm=\"http://{address}/token\",service=\"registry\"\r\n"
+                ),
+            )
+        }
+
+        #[tokio::test]
+        async fn exchange_identity_token_for_access_token() {
+            let (image, requests) = serve(|address| {
+                vec![
+                    challenge(address),
+                    response_with_body("200 OK", "", r#"{"access_token":"4cc355"}"#),
+                ]
+            })
+            .await;
+            let registry = OciRegistry::from_config(
+                http_config(),
+                RegistryAuth::Basic(String::from(IDENTITY_TOKEN_USER), String::from("r3fr3sh")),
+            )
+            .unwrap();
+
+            let registry = registry.exchange_identity_token(&[&image]).await.unwrap();
+
+            assert_eq!(
+                registry.registry_auth,
+                RegistryAuth::Bearer(String::from("4cc355"))
+            );
+            let requests = requests.lock().unwrap();
+            assert!(requests[0].starts_with("GET /v2/ "), "{}", requests[0]);
+            assert!(requests[1].starts_with("POST /token "), "{}", requests[1]);
+            assert!(
+                requests[1].ends_with(
+                    "grant_type=refresh_token&refresh_token=r3fr3sh&client_id=oci-semver-tagging&scope=repository%3Apostgres%3Apull%2Cpush%2Cdelete&service=registry"
+                ),
+                "{}",
+                requests[1]
+            );
+        }
+
+        #[tokio::test]
+        async fn refused_identity_token() {
+            let (image, _) =
+                serve(|address| vec![challenge(address), response("401 Unauthorized", "")]).await;
+            let registry = OciRegistry::from_config(
+                http_config(),
+                RegistryAuth::Basic(String::from(IDENTITY_TOKEN_USER), String::from("r3fr3sh")),
+            )
+            .unwrap();
+
+            let err = crate::Error::from(
+                registry
+                    .exchange_identity_token(&[&image])
+                    .await
+                    .err()
+                    .unwrap(),
+            );
+
+            assert_eq!(err.exit_code(), 3, "{err}");
+        }
+
+        #[tokio::test]
+        async fn keep_other_credentials() {
+            let image = Reference::from_str("localhost:1/postgres:16.8.0").unwrap();
+            let registry = OciRegistry::from_config(
+                http_config(),
+                RegistryAuth::Basic(String::from("foo"), String::from("bar")),
+            )
+            .unwrap();
+
+            let registry = registry.exchange_identity_token(&[&image]).await.unwrap();
+
+            assert_eq!(
+                registry.registry_auth,
+                RegistryAuth::Basic(String::from("foo"), String::from("bar"))
+            );
+        }
+    }
+
+    #[test]
+    fn ignore_basic_challenge() {
+        assert_eq!(BearerChallenge::parse(r#"Basic realm="registry""#), None);
+    }
+}
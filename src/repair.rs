@@ -0,0 +1,207 @@
+use crate::{registry::Registry, validate::ValidationError};
+use anyhow::Result;
+use oci_client::Reference;
+use semver::Version;
+use std::{collections::BTreeMap, str::FromStr as _};
+
+/// Turns the validation errors into the tags that have to be pushed as copies of the full
+/// versions' manifests, grouped by the full version. Errors that cannot be repaired automatically
+/// are returned as second element. Partial tags pointing to a manifest that no full version tag
+/// points to are never re-pointed because they may be the only reference to e.g. a hotfix that
+/// has been pushed by hand, which the registry's garbage collection would delete then.
+pub fn repair_actions<'a>(
+    errors: &'a [ValidationError],
+    tag_prefix: &Option<String>,
+    tag_suffix: &Option<String>,
+) -> (BTreeMap<Version, Vec<String>>, Vec<&'a ValidationError>) {
+    let mut actions = BTreeMap::<Version, Vec<String>>::new();
+    let mut unrepairable = Vec::new();
+
+    for error in errors {
+        if let ValidationError::PartialPointsToUnknownManifest { .. } = error {
+            unrepairable.push(error);
+            continue;
+        }
+
+        match (error.tag(tag_prefix, tag_suffix), error.expected_version()) {
+            (Some(tag), Some(version)) => {
+                actions.entry(version.clone()).or_default().push(tag);
+            }
+            _ => unrepairable.push(error),
+        }
+    }
+
+    (actions, unrepairable)
+}
+
+/// Turns the validation errors of the partial tags that still point to the yanked `version` into
+/// the tags that have to be pushed as copies of the next-highest remaining full versions. All other
+/// errors are left alone.
+pub fn rollback_actions(
+    errors: &[ValidationError],
+    version: &Version,
+    tag_prefix: &Option<String>,
+    tag_suffix: &Option<String>,
+) -> BTreeMap<Version, Vec<String>> {
+    let mut actions = BTreeMap::<Version, Vec<String>>::new();
+
+    for error in errors
+        .iter()
+        .filter(|e| e.actual_version() == Some(version))
+    {
+        if let (Some(tag), Some(expected_version)) =
+            (error.tag(tag_prefix, tag_suffix), error.expected_version())
+        {
+            actions
+                .entry(expected_version.clone())
+                .or_default()
+                .push(tag);
+        }
+    }
+
+    actions
+}
+
+pub async fn repair<R: Registry>(
+    registry: &R,
+    image: &Reference,
+    tag_prefix: &Option<String>,
+    tag_suffix: &Option<String>,
+    actions: BTreeMap<Version, Vec<String>>,
+    dry_run: bool,
+) -> Result<()> {
+    if actions.is_empty() {
+        println!("Nothing to repair");
+        return Ok(());
+    }
+
+    let mut result = Ok(());
+    for (version, tags) in actions {
+        let full_version_image = Reference::from_str(&format!(
+            "{}/{}:{}{version}{}",
+            image.registry(),
+            image.repository(),
+            tag_prefix.as_ref().map(|t| t.as_str()).unwrap_or(""),
+            tag_suffix.as_ref().map(|t| t.as_str()).unwrap_or("")
+        ))
+        .expect("Must be valid image string");
+
+        match crate::tag::tag(registry, &full_version_image, tags, dry_run).await {
+            Ok(report) => {
+                report.print();
+                if !report.is_success() {
+                    result = Err(crate::Error::Push {
+                        image: full_version_image.to_string(),
+                        failed_tags: report.failed_tags(),
+                    }
+                    .into());
+                }
+            }
+            Err(err) => {
+                eprintln!("Cannot repair tags pointing to {full_version_image}: {err:#}");
+                result = Err(err.context("Not all tags could be repaired."));
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PartialSemverVersion;
+
+    #[test]
+    fn repair_missing_and_miss_placed_tags() {
+        let errors = [
+            ValidationError::MissingMajor {
+                latest_version: Version::new(1, 2, 3),
+            },
+            ValidationError::MissPlaced {
+                major_or_major_minor: PartialSemverVersion::with_major_minor(1, 2),
+                should_point_to: Version::new(1, 2, 3),
+                pointing_to_instead: Version::new(1, 2, 2),
+            },
+            ValidationError::MissingMajorMinor {
+                latest_version: Version::new(1, 1, 0),
+            },
+        ];
+
+        let (actions, unrepairable) = repair_actions(&errors, &Some(String::from("v")), &None);
+
+        assert_eq!(
+            actions,
+            BTreeMap::from([
+                (Version::new(1, 1, 0), vec![String::from("v1.1")]),
+                (
+                    Version::new(1, 2, 3),
+                    vec![String::from("v1"), String::from("v1.2")]
+                ),
+            ])
+        );
+        assert!(unrepairable.is_empty());
+    }
+
+    #[test]
+    fn never_repair_full_versions_pointing_to_same_manifest() {
+        let errors = [ValidationError::FullVersionsPointingToSameManifests {
+            versions: vec![Version::new(1, 2, 2), Version::new(1, 2, 3)],
+        }];
+
+        let (actions, unrepairable) = repair_actions(&errors, &None, &None);
+
+        assert!(actions.is_empty());
+        assert_eq!(unrepairable, vec![&errors[0]]);
+    }
+
+    #[test]
+    fn never_repair_partial_pointing_to_unknown_manifest() {
+        let errors = [ValidationError::PartialPointsToUnknownManifest {
+            major_or_major_minor: PartialSemverVersion::with_major_minor(1, 2),
+            should_point_to: Version::new(1, 2, 3),
+            digest: String::from("sha256:hotfix"),
+        }];
+
+        let (actions, unrepairable) = repair_actions(&errors, &None, &None);
+
+        assert!(actions.is_empty());
+        assert_eq!(unrepairable, vec![&errors[0]]);
+    }
+
+    #[test]
+    fn roll_back_only_tags_pointing_to_yanked_version() {
+        let errors = [
+            ValidationError::MissPlaced {
+                major_or_major_minor: PartialSemverVersion::with_major(1),
+                should_point_to: Version::new(1, 4, 2),
+                pointing_to_instead: Version::new(1, 4, 3),
+            },
+            ValidationError::MissPlaced {
+                major_or_major_minor: PartialSemverVersion::with_major_minor(1, 4),
+                should_point_to: Version::new(1, 4, 2),
+                pointing_to_instead: Version::new(1, 4, 3),
+            },
+            ValidationError::MissPlacedLatest {
+                latest_tag: String::from("latest"),
+                should_point_to: Version::new(1, 4, 2),
+                pointing_to_instead: Some(Version::new(1, 4, 3)),
+            },
+            ValidationError::MissingMajorMinor {
+                latest_version: Version::new(1, 3, 0),
+            },
+        ];
+
+        assert_eq!(
+            rollback_actions(&errors, &Version::new(1, 4, 3), &None, &None),
+            BTreeMap::from([(
+                Version::new(1, 4, 2),
+                vec![
+                    String::from("1"),
+                    String::from("1.4"),
+                    String::from("latest")
+                ]
+            )])
+        );
+    }
+}
@@ -1,123 +1,450 @@
-use crate::PartialSemverVersion;
-use anyhow::{Context, Result};
-use oci_distribution::{secrets::RegistryAuth, Client, Reference};
+use crate::{registry::Registry, PartialSemverVersion};
+use anyhow::{anyhow, Result};
+use oci_client::Reference;
 use semver::{Version, VersionReq};
-use std::str::FromStr as _;
+use serde::Serialize;
+use std::{str::FromStr as _, sync::Arc};
 use tokio::task::JoinSet;
 
-pub async fn tag(
-    client: &Client,
-    registry_auth: &RegistryAuth,
+/// The result of tagging an image with partial semver tags.
+#[derive(Debug, Serialize)]
+pub struct TagReport {
+    /// The image whose manifest is copied.
+    pub source: String,
+    /// The digest of the source image's manifest if any tag had to be pushed.
+    pub source_digest: Option<String>,
+    pub tags: Vec<TagResult>,
+    /// Why the tags of the version to tag are pushed or skipped if they have been planned with
+    /// [`plan_tags`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub plan: Vec<PlannedTag>,
+    /// The inconsistencies found when reading the tags back from the registry after the push.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub verification_errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagResult {
+    pub tag: String,
+    pub image: String,
+    pub status: TagStatus,
+    /// The digest that the tag resolves to after it has been pushed.
+    pub digest: Option<String>,
+    #[serde(serialize_with = "serialize_error")]
+    pub error: Option<anyhow::Error>,
+}
+
+fn serialize_error<S: serde::Serializer>(
+    error: &Option<anyhow::Error>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match error {
+        Some(error) => serializer.collect_str(&format_args!("{error:#}")),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagStatus {
+    /// The tag would be pushed but the tool runs in dry-run mode.
+    Planned,
+    Pushed,
+    Failed,
+}
+
+impl TagReport {
+    /// The report for pushing the manifest of `image` under the given tags in the repository of
+    /// `target` before anything has been pushed.
+    pub fn planned(image: &Reference, target: &Reference, tags: Vec<String>) -> Self {
+        Self {
+            source: image.to_string(),
+            source_digest: None,
+            tags: tags
+                .into_iter()
+                .map(|tag| TagResult {
+                    image: tagged_image(target, &tag).to_string(),
+                    tag,
+                    status: TagStatus::Planned,
+                    digest: None,
+                    error: None,
+                })
+                .collect(),
+            plan: Vec::new(),
+            verification_errors: Vec::new(),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.tags.iter().all(|t| t.status != TagStatus::Failed)
+    }
+
+    /// The images of the tags that could not be pushed together with the registry's errors.
+    pub fn failed_tags(self) -> Vec<(String, anyhow::Error)> {
+        self.tags
+            .into_iter()
+            .filter(|t| t.status == TagStatus::Failed)
+            .map(|t| {
+                let error = t.error.unwrap_or_else(|| anyhow!("unknown error"));
+                (t.image, error)
+            })
+            .collect()
+    }
+
+    /// Prints the report in a human-readable form.
+    pub fn print(&self) {
+        for tag in &self.plan {
+            match &tag.decision {
+                TagDecision::Push => {}
+                TagDecision::AlreadyExists => {
+                    println!("Skipping {} because it exists already", tag.tag)
+                }
+                TagDecision::OwnedByNewerVersion { newer_version } => println!(
+                    "Skipping {} because the newer version {newer_version} owns it",
+                    tag.tag
+                ),
+                TagDecision::Yanked => {
+                    println!("Skipping {} because the version has been yanked", tag.tag)
+                }
+            }
+        }
+
+        if self.tags.is_empty() {
+            println!("Nothing to push");
+        }
+
+        for tag in &self.tags {
+            match (&tag.status, &tag.digest, &tag.error) {
+                (TagStatus::Planned, _, _) => {
+                    println!(
+                        "Will push manifest of {} as copy of {}",
+                        tag.image, self.source
+                    )
+                }
+                (TagStatus::Pushed, Some(digest), _) => {
+                    println!("Pushed manifest of {} as {digest}.", tag.image)
+                }
+                (TagStatus::Pushed, None, _) => println!("Pushed manifest of {}.", tag.image),
+                (TagStatus::Failed, _, Some(error)) => {
+                    eprintln!("Cannot push manifest of {}: {error:#}", tag.image)
+                }
+                (TagStatus::Failed, _, None) => {
+                    eprintln!("Cannot push manifest of {}: unknown error", tag.image)
+                }
+            }
+        }
+
+        for error in &self.verification_errors {
+            eprintln!("Verification failed: {error}");
+        }
+    }
+}
+
+pub async fn tag<R: Registry>(
+    registry: &R,
     image: &Reference,
-    existing_tags: &[PartialSemverVersion],
-    version_to_tag: Version,
-    tag_prefix: &Option<String>,
+    tags_to_push: Vec<String>,
+    dry_run: bool,
+) -> Result<TagReport> {
+    tag_into(registry, image, registry, image, tags_to_push, dry_run).await
+}
+
+/// Pushes the manifest of `image` under the given tags into the repository of `target`, which may
+/// be another repository or registry. In that case, the blobs and child manifests that are missing
+/// in the target repository are copied first.
+pub async fn tag_into<R: Registry>(
+    source_registry: &R,
+    image: &Reference,
+    target_registry: &R,
+    target: &Reference,
+    tags_to_push: Vec<String>,
     dry_run: bool,
-) -> Result<()> {
-    let tags_to_push = tags_to_push(version_to_tag, existing_tags, tag_prefix);
-    if tags_to_push.is_empty() {
-        println!("Nothing to push");
-        return Ok(());
+) -> Result<TagReport> {
+    let mut report = TagReport::planned(image, target, tags_to_push);
+    if report.tags.is_empty() {
+        return Ok(report);
+    }
+
+    let manifest = source_registry.pull_manifest(image).await?;
+    report.source_digest = Some(manifest.digest.clone());
+    if dry_run {
+        return Ok(report);
     }
 
-    let (baseline_manifest, _digest) = client
-        .pull_manifest(image, registry_auth)
-        .await
-        .with_context(|| format!("Cannot pull manifest for {}", image))?;
+    if image.resolve_registry() != target.resolve_registry()
+        || image.repository() != target.repository()
+    {
+        target_registry
+            .copy_references(source_registry, image, target, &manifest)
+            .await?;
+    }
 
+    let manifest = Arc::new(manifest);
     let mut set = JoinSet::new();
 
-    for tag in tags_to_push {
-        let tagged_image = Reference::from_str(&format!(
-            "{}/{}:{tag}",
-            image.registry(),
-            image.repository()
-        ))
-        .expect("Must be valid image string");
-
-        println!("Will push manifest of {tagged_image} as copy of {image}");
-
-        if !dry_run {
-            let client = client.clone();
-            let baseline_manifest = baseline_manifest.clone();
-            set.spawn(async move {
-                (
-                    client
-                        .push_manifest(&tagged_image, &baseline_manifest)
-                        .await,
-                    tagged_image,
-                )
-            });
-        }
+    for (index, tag) in report.tags.iter().enumerate() {
+        let tagged_image = tagged_image(target, &tag.tag);
+        let registry = target_registry.clone();
+        let manifest = Arc::clone(&manifest);
+        set.spawn(async move {
+            let result = async {
+                registry.push_manifest(&tagged_image, &manifest).await?;
+                let digest = registry
+                    .resolve_digest(&tagged_image)
+                    .await?
+                    .ok_or_else(|| anyhow!("{tagged_image} does not exist after the push"))?;
+                check_digest(&tagged_image, &digest, &manifest.digest)?;
+                Ok::<_, anyhow::Error>(digest)
+            }
+            .await;
+            (index, result)
+        });
     }
 
-    let mut result = Ok(());
     while let Some(res) = set.join_next().await {
         match res {
-            Ok((Ok(url), image)) => {
-                println!("Pushed manifest of {image} to {url}.");
+            Ok((index, Ok(digest))) => {
+                report.tags[index].status = TagStatus::Pushed;
+                report.tags[index].digest = Some(digest);
             }
-            Ok((Err(err), image)) => {
-                eprintln!("Cannot push manifest of {image}: {err}");
-                result = Err(err).with_context(|| format!("{image}"));
+            Ok((index, Err(err))) => {
+                report.tags[index].status = TagStatus::Failed;
+                report.tags[index].error = Some(err);
             }
-            Err(_err) => todo!(),
+            Err(err) => return Err(crate::Error::Join(err).into()),
         }
     }
 
-    result
+    Ok(report)
+}
+
+/// Fails if the pushed tag doesn't resolve to the manifest that has been pushed.
+fn check_digest(tagged_image: &Reference, digest: &str, source_digest: &str) -> Result<()> {
+    if digest == source_digest {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{tagged_image} resolves to {digest} instead of {source_digest} after the push"
+        ))
+    }
+}
+
+fn tagged_image(image: &Reference, tag: &str) -> Reference {
+    Reference::from_str(&format!(
+        "{}/{}:{tag}",
+        image.registry(),
+        image.repository()
+    ))
+    .expect("Must be valid image string")
+}
+
+/// The decisions of the tag planner for each tag that the version to tag could be pushed as.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TagPlan {
+    pub tags: Vec<PlannedTag>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PlannedTag {
+    pub tag: String,
+    #[serde(flatten)]
+    pub decision: TagDecision,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum TagDecision {
+    Push,
+    /// The full version tag exists already and won't be overwritten.
+    AlreadyExists,
+    /// The moving tag stays at the newer version.
+    OwnedByNewerVersion {
+        #[serde(serialize_with = "serialize_version")]
+        newer_version: Version,
+    },
+    /// The version to tag has been yanked, so the moving tag stays where it has been rolled back
+    /// to.
+    Yanked,
+}
+
+fn serialize_version<S: serde::Serializer>(
+    version: &Version,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(version)
+}
+
+impl TagPlan {
+    pub fn tags_to_push(&self) -> Vec<String> {
+        self.tags
+            .iter()
+            .filter(|t| t.decision == TagDecision::Push)
+            .map(|t| t.tag.clone())
+            .collect()
+    }
+
+    /// The moving tags that won't point to the version to tag because a newer version owns them.
+    pub fn owned_by_newer_versions(&self) -> impl Iterator<Item = (&str, &Version)> {
+        self.tags.iter().filter_map(|t| match &t.decision {
+            TagDecision::OwnedByNewerVersion { newer_version } => {
+                Some((t.tag.as_str(), newer_version))
+            }
+            _ => None,
+        })
+    }
 }
 
-fn tags_to_push(
+/// The tags of [`plan_tags`] that will be pushed.
+pub fn tags_to_push(
     version: Version,
     existing_tags: &[PartialSemverVersion],
     prefix: &Option<String>,
+    suffix: &Option<String>,
+    latest: &Option<String>,
+    pre_release_channels: bool,
+    yanked: &[Version],
 ) -> Vec<String> {
-    let mut tags = Vec::with_capacity(3);
+    plan_tags(
+        version,
+        existing_tags,
+        prefix,
+        suffix,
+        latest,
+        pre_release_channels,
+        yanked,
+    )
+    .tags_to_push()
+}
 
-    let prefix = prefix.as_ref().map(|s| s.as_str()).unwrap_or("");
-    if !existing_tags.iter().any(|v| v == &version) {
-        tags.push(format!(
-            "{prefix}{}.{}.{}",
-            version.major, version.minor, version.patch
-        ));
-    }
+/// Decides for the full, major.minor and major tag (as well as the latest and the channel tags if
+/// requested) of `version` whether they will be pushed or why they are skipped. Yanked versions
+/// never own moving tags, so that tagging doesn't undo a rollback.
+pub fn plan_tags(
+    version: Version,
+    existing_tags: &[PartialSemverVersion],
+    prefix: &Option<String>,
+    suffix: &Option<String>,
+    latest: &Option<String>,
+    pre_release_channels: bool,
+    yanked: &[Version],
+) -> TagPlan {
+    let mut tags = Vec::with_capacity(4);
 
-    let version_req = VersionReq::parse(&format!(
-        ">{major}.{minor}.{patch}, <{major}.{minor_next}, <{major_next}.0.0",
-        major = version.major,
-        minor = version.minor,
-        patch = version.patch,
-        major_next = version.major + 1,
-        minor_next = version.minor + 1
-    ))
-    .expect("Must be valid version requirement");
-    if !existing_tags
-        .iter()
-        .filter_map(|psv| psv.full())
-        .any(|v| version_req.matches(v))
-    {
-        tags.push(format!("{prefix}{}.{}", version.major, version.minor));
+    let prefix = prefix.as_ref().map(|s| s.as_str()).unwrap_or("");
+    let suffix = suffix.as_ref().map(|s| s.as_str()).unwrap_or("");
 
-        let version_req = VersionReq::parse(&format!(
-            ">{major}.{minor}, <{major_next}.0.0",
-            major = version.major,
-            minor = version.minor,
-            major_next = version.major + 1
-        ))
-        .expect("Must be valid version requirement");
-        if !existing_tags
+    let is_yanked = yanked.contains(&version);
+    let moving_tag = |tag: String, newest_in_line: Option<&Version>| PlannedTag {
+        tag,
+        decision: match newest_in_line {
+            _ if is_yanked => TagDecision::Yanked,
+            Some(newer_version) => TagDecision::OwnedByNewerVersion {
+                newer_version: newer_version.clone(),
+            },
+            None => TagDecision::Push,
+        },
+    };
+    let not_yanked_versions = || {
+        existing_tags
             .iter()
             .filter_map(|psv| psv.full())
-            .any(|v| version_req.matches(v))
-        {
-            tags.push(format!("{prefix}{}", version.major));
+            .filter(|v| !yanked.contains(v))
+    };
+
+    tags.push(PlannedTag {
+        tag: format!("{prefix}{version}{suffix}"),
+        decision: if existing_tags.iter().any(|v| v == &version) {
+            TagDecision::AlreadyExists
+        } else {
+            TagDecision::Push
+        },
+    });
+
+    if !version.pre.is_empty() {
+        // Pre-releases never move the stable partial tags but only the partial tags of their
+        // channel, e.g. 2.0-rc and 2-rc for 2.0.0-rc.1.
+        if pre_release_channels {
+            let channel = PartialSemverVersion::from(version.clone())
+                .channel()
+                .expect("Pre-release must have a channel")
+                .to_string();
+            let newest_in_channel = |same_minor: bool| {
+                not_yanked_versions()
+                    .filter(|v| {
+                        v.major == version.major && (!same_minor || v.minor == version.minor)
+                    })
+                    .filter(|v| {
+                        PartialSemverVersion::from((*v).clone()).channel() == Some(channel.as_str())
+                    })
+                    .filter(|v| *v > &version)
+                    .max()
+            };
+
+            tags.push(moving_tag(
+                format!(
+                    "{prefix}{}.{}-{channel}{suffix}",
+                    version.major, version.minor
+                ),
+                newest_in_channel(true),
+            ));
+            tags.push(moving_tag(
+                format!("{prefix}{}-{channel}{suffix}", version.major),
+                newest_in_channel(false),
+            ));
         }
+
+        tags.reverse();
+
+        return TagPlan { tags };
+    }
+
+    let newest_stable = |version_req: VersionReq| {
+        not_yanked_versions()
+            .filter(|v| version_req.matches(v))
+            .max()
+    };
+
+    tags.push(moving_tag(
+        format!("{prefix}{}.{}{suffix}", version.major, version.minor),
+        newest_stable(
+            VersionReq::parse(&format!(
+                ">{major}.{minor}.{patch}, <{major}.{minor_next}, <{major_next}.0.0",
+                major = version.major,
+                minor = version.minor,
+                patch = version.patch,
+                major_next = version.major + 1,
+                minor_next = version.minor + 1
+            ))
+            .expect("Must be valid version requirement"),
+        ),
+    ));
+
+    tags.push(moving_tag(
+        format!("{prefix}{}{suffix}", version.major),
+        newest_stable(
+            VersionReq::parse(&format!(
+                ">{major}.{minor}.{patch}, <{major_next}.0.0",
+                major = version.major,
+                minor = version.minor,
+                patch = version.patch,
+                major_next = version.major + 1
+            ))
+            .expect("Must be valid version requirement"),
+        ),
+    ));
+
+    if let Some(latest) = latest {
+        tags.push(moving_tag(
+            latest.clone(),
+            not_yanked_versions()
+                .filter(|v| v.pre.is_empty() && *v > &version)
+                .max(),
+        ));
     }
 
     tags.reverse();
 
-    tags
+    TagPlan { tags }
 }
 
 #[cfg(test)]
@@ -127,7 +454,15 @@ mod tests {
     #[test]
     fn push_all_tags_if_no_version_exists() {
         assert_eq!(
-            tags_to_push(Version::from_str("1.0.0").unwrap(), &[], &None),
+            tags_to_push(
+                Version::from_str("1.0.0").unwrap(),
+                &[],
+                &None,
+                &None,
+                &None,
+                false,
+                &[],
+            ),
             vec![
                 String::from("1"),
                 String::from("1.0"),
@@ -142,7 +477,11 @@ mod tests {
             tags_to_push(
                 Version::from_str("1.0.0").unwrap(),
                 &[PartialSemverVersion::from_str("1.0.0").unwrap()],
-                &None
+                &None,
+                &None,
+                &None,
+                false,
+                &[],
             ),
             vec![String::from("1"), String::from("1.0")]
         );
@@ -154,7 +493,11 @@ mod tests {
             tags_to_push(
                 Version::from_str("1.2.3").unwrap(),
                 &[PartialSemverVersion::from_str("3.2.1").unwrap()],
-                &None
+                &None,
+                &None,
+                &None,
+                false,
+                &[],
             ),
             vec![
                 String::from("1"),
@@ -171,6 +514,10 @@ mod tests {
                 Version::from_str("1.2.3").unwrap(),
                 &[PartialSemverVersion::from_str("3.2.1").unwrap()],
                 &Some(String::from("v")),
+                &None,
+                &None,
+                false,
+                &[],
             ),
             vec![
                 String::from("v1"),
@@ -189,7 +536,11 @@ mod tests {
                     PartialSemverVersion::from_str("1.3.3").unwrap(),
                     PartialSemverVersion::from_str("3.2.1").unwrap()
                 ],
-                &None
+                &None,
+                &None,
+                &None,
+                false,
+                &[],
             ),
             vec![String::from("1.2"), String::from("1.2.3")]
         )
@@ -204,9 +555,447 @@ mod tests {
                     PartialSemverVersion::from_str("1.2.3").unwrap(),
                     PartialSemverVersion::from_str("1.2.4").unwrap()
                 ],
-                &None
+                &None,
+                &None,
+                &None,
+                false,
+                &[],
             ),
             Vec::<String>::new()
         )
     }
+
+    #[test]
+    fn push_only_full_tag_for_pre_release() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("2.0.0-rc.1").unwrap(),
+                &[PartialSemverVersion::from_str("1.2.3").unwrap()],
+                &None,
+                &None,
+                &None,
+                false,
+                &[],
+            ),
+            vec![String::from("2.0.0-rc.1")]
+        )
+    }
+
+    #[test]
+    fn push_channel_tags_for_pre_release() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("2.0.0-rc.2").unwrap(),
+                &[
+                    PartialSemverVersion::from_str("1.2.3").unwrap(),
+                    PartialSemverVersion::from_str("2.0.0-rc.1").unwrap(),
+                    PartialSemverVersion::from_str("2.0.0-beta.3").unwrap()
+                ],
+                &None,
+                &None,
+                &None,
+                true,
+                &[],
+            ),
+            vec![
+                String::from("2-rc"),
+                String::from("2.0-rc"),
+                String::from("2.0.0-rc.2")
+            ]
+        )
+    }
+
+    #[test]
+    fn push_no_channel_tags_if_later_pre_release_in_channel_exists() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("2.0.0-rc.1").unwrap(),
+                &[
+                    PartialSemverVersion::from_str("2.0.0-rc.1").unwrap(),
+                    PartialSemverVersion::from_str("2.0.0-rc.2").unwrap()
+                ],
+                &None,
+                &None,
+                &None,
+                true,
+                &[],
+            ),
+            Vec::<String>::new()
+        )
+    }
+
+    #[test]
+    fn stable_release_ignores_pre_releases() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("1.2.3").unwrap(),
+                &[PartialSemverVersion::from_str("1.3.0-rc.1").unwrap()],
+                &None,
+                &None,
+                &None,
+                true,
+                &[],
+            ),
+            vec![
+                String::from("1"),
+                String::from("1.2"),
+                String::from("1.2.3")
+            ]
+        )
+    }
+
+    #[test]
+    fn push_variant_tags_with_suffix() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("1.2.3").unwrap(),
+                &[PartialSemverVersion::from_str("1.2.2").unwrap()],
+                &Some(String::from("v")),
+                &Some(String::from("-alpine")),
+                &None,
+                false,
+                &[],
+            ),
+            vec![
+                String::from("v1-alpine"),
+                String::from("v1.2-alpine"),
+                String::from("v1.2.3-alpine")
+            ]
+        )
+    }
+
+    #[test]
+    fn push_latest_if_highest_stable_version() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("1.2.3").unwrap(),
+                &[
+                    PartialSemverVersion::from_str("1.2.2").unwrap(),
+                    PartialSemverVersion::from_str("2.0.0-rc.1").unwrap()
+                ],
+                &None,
+                &None,
+                &Some(String::from("latest")),
+                false,
+                &[],
+            ),
+            vec![
+                String::from("latest"),
+                String::from("1"),
+                String::from("1.2"),
+                String::from("1.2.3")
+            ]
+        )
+    }
+
+    #[test]
+    fn push_no_latest_if_higher_major_exists() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("1.2.3").unwrap(),
+                &[PartialSemverVersion::from_str("2.0.0").unwrap()],
+                &None,
+                &None,
+                &Some(String::from("latest")),
+                false,
+                &[],
+            ),
+            vec![
+                String::from("1"),
+                String::from("1.2"),
+                String::from("1.2.3")
+            ]
+        )
+    }
+
+    #[test]
+    fn explain_why_tags_are_skipped() {
+        assert_eq!(
+            plan_tags(
+                Version::from_str("1.2.3").unwrap(),
+                &[
+                    PartialSemverVersion::from_str("1.2.3").unwrap(),
+                    PartialSemverVersion::from_str("1.2.4").unwrap(),
+                    PartialSemverVersion::from_str("1.2.5").unwrap(),
+                    PartialSemverVersion::from_str("1.3.0").unwrap(),
+                ],
+                &None,
+                &None,
+                &Some(String::from("latest")),
+                false,
+                &[],
+            ),
+            TagPlan {
+                tags: vec![
+                    PlannedTag {
+                        tag: String::from("latest"),
+                        decision: TagDecision::OwnedByNewerVersion {
+                            newer_version: Version::new(1, 3, 0)
+                        }
+                    },
+                    PlannedTag {
+                        tag: String::from("1"),
+                        decision: TagDecision::OwnedByNewerVersion {
+                            newer_version: Version::new(1, 3, 0)
+                        }
+                    },
+                    PlannedTag {
+                        tag: String::from("1.2"),
+                        decision: TagDecision::OwnedByNewerVersion {
+                            newer_version: Version::new(1, 2, 5)
+                        }
+                    },
+                    PlannedTag {
+                        tag: String::from("1.2.3"),
+                        decision: TagDecision::AlreadyExists
+                    },
+                ]
+            }
+        )
+    }
+
+    #[test]
+    fn explain_why_channel_tags_are_skipped() {
+        let plan = plan_tags(
+            Version::from_str("2.0.0-rc.1").unwrap(),
+            &[
+                PartialSemverVersion::from_str("2.0.0-rc.2").unwrap(),
+                PartialSemverVersion::from_str("2.1.0-rc.1").unwrap(),
+            ],
+            &None,
+            &None,
+            &None,
+            true,
+            &[],
+        );
+
+        assert_eq!(
+            plan.owned_by_newer_versions().collect::<Vec<_>>(),
+            vec![
+                ("2-rc", &Version::from_str("2.1.0-rc.1").unwrap()),
+                ("2.0-rc", &Version::from_str("2.0.0-rc.2").unwrap()),
+            ]
+        );
+        assert_eq!(plan.tags_to_push(), vec![String::from("2.0.0-rc.1")]);
+    }
+
+    #[test]
+    fn push_moving_tags_if_newer_version_is_yanked() {
+        assert_eq!(
+            tags_to_push(
+                Version::from_str("1.2.4").unwrap(),
+                &[
+                    PartialSemverVersion::from_str("1.2.4").unwrap(),
+                    PartialSemverVersion::from_str("1.2.5").unwrap()
+                ],
+                &None,
+                &None,
+                &Some(String::from("latest")),
+                false,
+                &[Version::new(1, 2, 5)],
+            ),
+            vec![
+                String::from("latest"),
+                String::from("1"),
+                String::from("1.2")
+            ]
+        )
+    }
+
+    #[test]
+    fn push_no_moving_tags_for_yanked_version() {
+        let plan = plan_tags(
+            Version::from_str("1.2.5").unwrap(),
+            &[
+                PartialSemverVersion::from_str("1.2.4").unwrap(),
+                PartialSemverVersion::from_str("1.2.5").unwrap(),
+            ],
+            &None,
+            &None,
+            &Some(String::from("latest")),
+            false,
+            &[Version::new(1, 2, 5)],
+        );
+
+        assert_eq!(
+            plan.tags
+                .iter()
+                .map(|t| (t.tag.as_str(), &t.decision))
+                .collect::<Vec<_>>(),
+            vec![
+                ("latest", &TagDecision::Yanked),
+                ("1", &TagDecision::Yanked),
+                ("1.2", &TagDecision::Yanked),
+                ("1.2.5", &TagDecision::AlreadyExists),
+            ]
+        );
+    }
+
+    #[test]
+    fn serialize_tag_plan() {
+        let plan = TagPlan {
+            tags: vec![
+                PlannedTag {
+                    tag: String::from("1.2"),
+                    decision: TagDecision::OwnedByNewerVersion {
+                        newer_version: Version::new(1, 2, 5),
+                    },
+                },
+                PlannedTag {
+                    tag: String::from("1.2.3"),
+                    decision: TagDecision::Push,
+                },
+            ],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&plan.tags).unwrap(),
+            serde_json::json!([{
+                "tag": "1.2",
+                "decision": "owned_by_newer_version",
+                "newer_version": "1.2.5"
+            }, {
+                "tag": "1.2.3",
+                "decision": "push"
+            }])
+        );
+    }
+
+    #[test]
+    fn serialize_tag_report() {
+        let report = TagReport {
+            source: String::from("localhost:5000/postgres:16.8.0"),
+            source_digest: Some(String::from("sha256:abc")),
+            tags: vec![TagResult {
+                tag: String::from("16"),
+                image: String::from("localhost:5000/postgres:16"),
+                status: TagStatus::Pushed,
+                digest: Some(String::from("sha256:abc")),
+                error: None,
+            }],
+            plan: Vec::new(),
+            verification_errors: Vec::new(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "source": "localhost:5000/postgres:16.8.0",
+                "source_digest": "sha256:abc",
+                "tags": [{
+                    "tag": "16",
+                    "image": "localhost:5000/postgres:16",
+                    "status": "pushed",
+                    "digest": "sha256:abc",
+                    "error": null
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn fail_if_pushed_tag_resolves_to_different_digest() {
+        let image = Reference::from_str("localhost:5000/postgres:16").unwrap();
+
+        assert!(check_digest(&image, "sha256:abc", "sha256:abc").is_ok());
+        assert_eq!(
+            check_digest(&image, "sha256:def", "sha256:abc")
+                .unwrap_err()
+                .to_string(),
+            "localhost:5000/postgres:16 resolves to sha256:def instead of sha256:abc after the push"
+        );
+    }
+
+    mod in_memory {
+        use super::*;
+        use crate::registry::{image, registry_with_tags};
+
+        #[tokio::test]
+        async fn push_partial_tags() {
+            let registry = registry_with_tags(&[("16.8.0", "sha256:168")]);
+
+            let report = tag(
+                &registry,
+                &image("16.8.0"),
+                vec![String::from("16"), String::from("16.8")],
+                false,
+            )
+            .await
+            .unwrap();
+
+            assert!(report.is_success());
+            assert_eq!(report.source_digest.as_deref(), Some("sha256:168"));
+            for tag in ["16", "16.8"] {
+                assert_eq!(
+                    registry.resolve_digest(&image(tag)).await.unwrap(),
+                    Some(String::from("sha256:168"))
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn report_partial_push() {
+            let registry = registry_with_tags(&[("16.8.0", "sha256:168")]);
+            registry.fail_pushes_to(&image("16"));
+
+            let report = tag(
+                &registry,
+                &image("16.8.0"),
+                vec![String::from("16"), String::from("16.8")],
+                false,
+            )
+            .await
+            .unwrap();
+
+            assert!(!report.is_success());
+            assert_eq!(registry.resolve_digest(&image("16")).await.unwrap(), None);
+            assert_eq!(
+                registry.resolve_digest(&image("16.8")).await.unwrap(),
+                Some(String::from("sha256:168"))
+            );
+            assert_eq!(
+                report
+                    .failed_tags()
+                    .into_iter()
+                    .map(|(image, _)| image)
+                    .collect::<Vec<_>>(),
+                vec![String::from("localhost:5000/postgres:16")]
+            );
+        }
+
+        #[tokio::test]
+        async fn push_nothing_in_dry_run() {
+            let registry = registry_with_tags(&[("16.8.0", "sha256:168")]);
+
+            let report = tag(&registry, &image("16.8.0"), vec![String::from("16")], true)
+                .await
+                .unwrap();
+
+            assert_eq!(report.tags[0].status, TagStatus::Planned);
+            assert_eq!(registry.resolve_digest(&image("16")).await.unwrap(), None);
+        }
+
+        #[tokio::test]
+        async fn tag_into_other_repository() {
+            let registry = registry_with_tags(&[("16.8.0", "sha256:168")]);
+            let target = Reference::from_str("localhost:5000/production/postgres").unwrap();
+
+            let report = tag_into(
+                &registry,
+                &image("16.8.0"),
+                &registry,
+                &target,
+                vec![String::from("16.8.0"), String::from("16")],
+                false,
+            )
+            .await
+            .unwrap();
+
+            assert!(report.is_success());
+            assert_eq!(
+                registry.list_tags(&target).await.unwrap(),
+                vec![String::from("16"), String::from("16.8.0")]
+            );
+        }
+    }
 }
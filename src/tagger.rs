@@ -0,0 +1,157 @@
+use crate::{
+    apply::{self, ApplyPlan},
+    registry::{OciRegistry, Registry},
+    tag::{self, TagReport},
+    validate::{self, ValidationError},
+    Error,
+};
+use oci_client::{client::ClientConfig, secrets::RegistryAuth, Reference};
+use semver::Version;
+use std::str::FromStr as _;
+
+/// The options that the tag and validate commands take from the command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TagOptions {
+    /// A prefix that is put in front of the tags, e.g. `v`.
+    pub prefix: Option<String>,
+    /// A suffix that is put behind the tags, e.g. `-alpine` for variants of the image.
+    pub suffix: Option<String>,
+    /// The tag, e.g. `latest`, that is moved to the highest stable version.
+    pub latest: Option<String>,
+    /// If the moving channel tags such as 2.0-rc and 2-rc are pushed for pre-releases.
+    pub pre_release_channels: bool,
+    /// Versions that partial tags must not point to anymore, see the rollback command.
+    pub yanked: Vec<Version>,
+}
+
+/// Tags images with partial semver tags without going through the command line, e.g. when the
+/// tool is embedded into a release service. The registry can be replaced, e.g. by an
+/// [`InMemoryRegistry`](crate::InMemoryRegistry) in tests.
+///
+/// ```no_run
+/// # use oci_client::{client::ClientConfig, secrets::RegistryAuth, Reference};
+/// # use oci_semver_tagging::{SemverTagger, TagOptions};
+/// # async fn release() -> Result<(), oci_semver_tagging::Error> {
+/// let tagger = SemverTagger::new(
+///     ClientConfig::default(),
+///     RegistryAuth::Anonymous,
+///     TagOptions::default(),
+/// )?;
+/// let image = Reference::try_from("localhost:5000/postgres:16.8.0").unwrap();
+///
+/// let plan = tagger.plan(&image, &semver::Version::new(16, 8, 0)).await?;
+/// let report = tagger.apply(&plan).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SemverTagger<R = OciRegistry> {
+    registry: R,
+    options: TagOptions,
+}
+
+impl SemverTagger {
+    /// Creates the tagger for the registry client that `config` describes. The config also applies
+    /// to the requests that the oci-client doesn't support, i.e. listing tags and deleting.
+    pub fn new(
+        config: ClientConfig,
+        registry_auth: RegistryAuth,
+        options: TagOptions,
+    ) -> Result<Self, Error> {
+        Ok(Self::with_registry(
+            OciRegistry::from_config(config, registry_auth)?,
+            options,
+        ))
+    }
+}
+
+impl<R: Registry> SemverTagger<R> {
+    pub fn with_registry(registry: R, options: TagOptions) -> Self {
+        Self { registry, options }
+    }
+
+    /// Computes which tags `image` will be pushed as if it is tagged as `version` and records the
+    /// digests that the source image and the tags point to, so that [`SemverTagger::apply`]
+    /// refuses to push if the registry has been changed in the meantime.
+    pub async fn plan(&self, image: &Reference, version: &Version) -> Result<ApplyPlan, Error> {
+        let existing_tags = self.existing_tags(image).await?;
+
+        let tags_to_push = tag::tags_to_push(
+            version.clone(),
+            &existing_tags,
+            &self.options.prefix,
+            &self.options.suffix,
+            &self.options.latest,
+            self.options.pre_release_channels,
+            &self.options.yanked,
+        );
+
+        Ok(apply::plan(&self.registry, image, tags_to_push).await?)
+    }
+
+    /// Pushes the tags of the plan. Tags that could not be pushed are reported as failed instead
+    /// of failing the whole call.
+    pub async fn apply(&self, plan: &ApplyPlan) -> Result<TagReport, Error> {
+        Ok(apply::apply(&self.registry, plan, false).await?)
+    }
+
+    /// Validates the partial semver tags of the repository of `image`.
+    pub async fn validate(&self, image: &Reference) -> Result<Vec<ValidationError>, Error> {
+        let existing_tags = self.existing_tags(image).await?;
+
+        Ok(validate::validation_errors(
+            &self.registry,
+            image,
+            &self.options.prefix,
+            &self.options.suffix,
+            &self.options.latest,
+            &self.options.yanked,
+            &existing_tags,
+        )
+        .await?)
+    }
+
+    async fn existing_tags(
+        &self,
+        image: &Reference,
+    ) -> anyhow::Result<Vec<crate::PartialSemverVersion>> {
+        crate::present_partial_semver_tags(
+            &self.registry,
+            &Reference::from_str(&format!("{}/{}", image.registry(), image.repository()))
+                .expect("Must be valid image string"),
+            &self.options.prefix,
+            &self.options.suffix,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{image, registry_with_tags};
+
+    #[tokio::test]
+    async fn plan_no_moving_tags_for_yanked_version() {
+        let registry = registry_with_tags(&[
+            ("16.8.0", "sha256:168"),
+            ("16.8.1", "sha256:1681"),
+            ("16.8", "sha256:168"),
+            ("16", "sha256:168"),
+        ]);
+        let tagger = SemverTagger::with_registry(
+            registry,
+            TagOptions {
+                yanked: vec![Version::new(16, 8, 1)],
+                ..Default::default()
+            },
+        );
+
+        let plan = tagger
+            .plan(&image("16.8.1"), &Version::new(16, 8, 1))
+            .await
+            .unwrap();
+
+        assert!(plan.targets.is_empty());
+        assert!(tagger.validate(&image("16.8.1")).await.unwrap().is_empty());
+    }
+}
@@ -1,8 +1,11 @@
-use crate::PartialSemverVersion;
-use anyhow::{Context, Result};
-use oci_distribution::{manifest::OciManifest, secrets::RegistryAuth, Client, Reference};
+use crate::{registry::Registry, PartialSemverVersion};
+use anyhow::{anyhow, Result};
+use oci_client::{
+    errors::{OciDistributionError, OciErrorCode},
+    Reference,
+};
 use semver::Version;
-use sha2::Digest;
+use serde::Serialize;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     fmt::Display,
@@ -10,37 +13,32 @@ use std::{
 };
 use tokio::task::JoinSet;
 
-pub async fn validate(
-    client: &Client,
-    registry_auth: &RegistryAuth,
+pub async fn validation_errors<R: Registry>(
+    registry: &R,
     image: &Reference,
     tag_prefix: &Option<String>,
+    tag_suffix: &Option<String>,
+    latest: &Option<String>,
+    yanked: &[Version],
     existing_tags: &[PartialSemverVersion],
-) -> Result<()> {
-    println!(
-        "Validating for {image} if the tags have correct partial semver tagging: {}",
-        existing_tags
-            .iter()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>()
-            .join(", ")
-    );
+) -> Result<Vec<ValidationError>> {
+    let digests = fetch_digests(registry, image, tag_prefix, tag_suffix, existing_tags).await?;
 
-    let manifests =
-        fetch_manifests(client, registry_auth, image, tag_prefix, existing_tags).await?;
+    let latest = match latest {
+        Some(name) => Some(LatestTag {
+            name: name.clone(),
+            digest: fetch_optional_digest(registry, image, name).await?,
+        }),
+        None => None,
+    };
 
-    detect_miss_placed_tags(existing_tags, manifests).map_err(|errors| {
-        anyhow::anyhow!(errors
-            .iter()
-            .map(|e| e.to_string())
-            .collect::<Vec<_>>()
-            .join("\n"))
-    })?;
-    Ok(())
+    Ok(detect_miss_placed_tags(digests, latest, yanked)
+        .err()
+        .unwrap_or_default())
 }
 
-#[derive(thiserror::Error, Debug, PartialEq, Eq)]
-enum ValidationError {
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
     MissingMajor {
         latest_version: Version,
     },
@@ -55,6 +53,201 @@ enum ValidationError {
     FullVersionsPointingToSameManifests {
         versions: Vec<Version>,
     },
+    MissingLatest {
+        latest_tag: String,
+        latest_version: Version,
+    },
+    MissPlacedLatest {
+        latest_tag: String,
+        should_point_to: Version,
+        pointing_to_instead: Option<Version>,
+    },
+    /// The partial tag points to a manifest that no full version tag points to, e.g. a hotfix that
+    /// has been pushed by hand or a full version tag that has been deleted.
+    PartialPointsToUnknownManifest {
+        major_or_major_minor: PartialSemverVersion,
+        should_point_to: Version,
+        digest: String,
+    },
+    /// Like [`ValidationError::PartialPointsToUnknownManifest`] but all versions of the partial
+    /// tag's range have been yanked, so there is no version that it should point to instead.
+    OrphanedPartial {
+        major_or_major_minor: PartialSemverVersion,
+        digest: String,
+    },
+    /// There is no full version in the range of the partial tag at all, e.g. because the full
+    /// version tags have been deleted.
+    DanglingPartial {
+        major_or_major_minor: PartialSemverVersion,
+    },
+    /// Like [`ValidationError::MissPlaced`] for a major tag that points into another minor line
+    /// than the highest one of its range, e.g. 32 pointing to 32.0.1 while there is 32.1.0.
+    MajorPointsIntoOtherMinor {
+        major: PartialSemverVersion,
+        should_point_to: Version,
+        pointing_to_instead: Version,
+    },
+}
+
+impl ValidationError {
+    /// A stable identifier of the kind of error for machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::MissingMajor { .. } => "missing_major",
+            Self::MissingMajorMinor { .. } => "missing_major_minor",
+            Self::MissPlaced { .. } => "miss_placed",
+            Self::FullVersionsPointingToSameManifests { .. } => {
+                "full_versions_pointing_to_same_manifests"
+            }
+            Self::MissingLatest { .. } => "missing_latest",
+            Self::MissPlacedLatest { .. } => "miss_placed_latest",
+            Self::PartialPointsToUnknownManifest { .. } => "partial_points_to_unknown_manifest",
+            Self::OrphanedPartial { .. } => "orphaned_partial",
+            Self::DanglingPartial { .. } => "dangling_partial",
+            Self::MajorPointsIntoOtherMinor { .. } => "major_points_into_other_minor",
+        }
+    }
+
+    /// The tag that is missing or misplaced including prefix and suffix.
+    pub fn tag(&self, tag_prefix: &Option<String>, tag_suffix: &Option<String>) -> Option<String> {
+        let partial_tag = match self {
+            Self::MissingMajor { latest_version } => {
+                PartialSemverVersion::from(latest_version.clone()).to_major()
+            }
+            Self::MissingMajorMinor { latest_version } => {
+                PartialSemverVersion::from(latest_version.clone())
+                    .to_major_minor()
+                    .expect("full must be convertible to major.minor")
+            }
+            Self::MissPlaced {
+                major_or_major_minor,
+                ..
+            }
+            | Self::PartialPointsToUnknownManifest {
+                major_or_major_minor,
+                ..
+            }
+            | Self::OrphanedPartial {
+                major_or_major_minor,
+                ..
+            }
+            | Self::DanglingPartial {
+                major_or_major_minor,
+            }
+            | Self::MajorPointsIntoOtherMinor {
+                major: major_or_major_minor,
+                ..
+            } => major_or_major_minor.clone(),
+            Self::FullVersionsPointingToSameManifests { .. } => return None,
+            Self::MissingLatest { latest_tag, .. } | Self::MissPlacedLatest { latest_tag, .. } => {
+                return Some(latest_tag.clone())
+            }
+        };
+
+        Some(format!(
+            "{}{partial_tag}{}",
+            tag_prefix.as_ref().map(|t| t.as_str()).unwrap_or(""),
+            tag_suffix.as_ref().map(|t| t.as_str()).unwrap_or("")
+        ))
+    }
+
+    /// The full version that the tag should point to.
+    pub fn expected_version(&self) -> Option<&Version> {
+        match self {
+            Self::MissingMajor { latest_version }
+            | Self::MissingMajorMinor { latest_version }
+            | Self::MissingLatest { latest_version, .. } => Some(latest_version),
+            Self::MissPlaced {
+                should_point_to, ..
+            }
+            | Self::MissPlacedLatest {
+                should_point_to, ..
+            }
+            | Self::PartialPointsToUnknownManifest {
+                should_point_to, ..
+            }
+            | Self::MajorPointsIntoOtherMinor {
+                should_point_to, ..
+            } => Some(should_point_to),
+            Self::FullVersionsPointingToSameManifests { .. }
+            | Self::OrphanedPartial { .. }
+            | Self::DanglingPartial { .. } => None,
+        }
+    }
+
+    /// The full version that the tag points to instead.
+    pub fn actual_version(&self) -> Option<&Version> {
+        match self {
+            Self::MissPlaced {
+                pointing_to_instead,
+                ..
+            }
+            | Self::MajorPointsIntoOtherMinor {
+                pointing_to_instead,
+                ..
+            } => Some(pointing_to_instead),
+            Self::MissPlacedLatest {
+                pointing_to_instead,
+                ..
+            } => pointing_to_instead.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn report(
+        &self,
+        tag_prefix: &Option<String>,
+        tag_suffix: &Option<String>,
+    ) -> ValidationErrorReport {
+        ValidationErrorReport {
+            kind: self.kind(),
+            partial_tag: self.tag(tag_prefix, tag_suffix),
+            expected_version: self.expected_version().map(ToString::to_string),
+            actual_version: self.actual_version().map(ToString::to_string),
+            digest: match self {
+                Self::PartialPointsToUnknownManifest { digest, .. }
+                | Self::OrphanedPartial { digest, .. } => Some(digest.clone()),
+                _ => None,
+            },
+            versions: match self {
+                Self::FullVersionsPointingToSameManifests { versions } => {
+                    versions.iter().map(ToString::to_string).collect()
+                }
+                _ => Vec::new(),
+            },
+            message: self.to_string(),
+        }
+    }
+}
+
+/// The machine-readable representation of a [`ValidationError`].
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ValidationErrorReport {
+    kind: &'static str,
+    partial_tag: Option<String>,
+    expected_version: Option<String>,
+    actual_version: Option<String>,
+    /// The digest of the unknown manifest that the partial tag points to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<String>,
+    versions: Vec<String>,
+    message: String,
+}
+
+/// The machine-readable result of the validate command.
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    pub image: String,
+    pub tags: Vec<String>,
+    pub errors: Vec<ValidationErrorReport>,
+}
+
+/// The tag, e.g. `latest`, that shall point to the highest stable version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatestTag {
+    pub name: String,
+    /// The digest that the tag points to or none if it doesn't exist.
+    pub digest: Option<String>,
 }
 
 impl Display for ValidationError {
@@ -68,32 +261,54 @@ impl Display for ValidationError {
                 pointing_to_instead,
             } => write!(f,"The {major_or_major_minor} tag points to {pointing_to_instead} instead to {should_point_to}"),
             Self::FullVersionsPointingToSameManifests { versions } => write!(f,"The tags {} point to the same manifest", versions.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")),
+            Self::MissingLatest { latest_tag, latest_version } => write!(f,"There is no tag '{latest_tag}' for '{latest_version}'"),
+            Self::MissPlacedLatest {
+                latest_tag,
+                should_point_to,
+                pointing_to_instead: Some(pointing_to_instead),
+            } => write!(f,"The {latest_tag} tag points to {pointing_to_instead} instead to {should_point_to}"),
+            Self::MissPlacedLatest {
+                latest_tag,
+                should_point_to,
+                pointing_to_instead: None,
+            } => write!(f,"The {latest_tag} tag points to none of the versions instead to {should_point_to}"),
+            Self::PartialPointsToUnknownManifest {
+                major_or_major_minor,
+                should_point_to,
+                digest,
+            } => write!(f,"The {major_or_major_minor} tag points to the manifest {digest} that no full version points to instead to {should_point_to}"),
+            Self::OrphanedPartial {
+                major_or_major_minor,
+                digest,
+            } => write!(f,"The {major_or_major_minor} tag points to the manifest {digest} that no full version points to and all versions of its range have been yanked"),
+            Self::DanglingPartial { major_or_major_minor } => write!(f,"There is no full version for the {major_or_major_minor} tag"),
+            Self::MajorPointsIntoOtherMinor {
+                major,
+                should_point_to,
+                pointing_to_instead,
+            } => write!(f,"The {major} tag points to {pointing_to_instead} of another minor line instead to {should_point_to} of the highest minor line"),
         }
     }
 }
 
-fn detect_miss_placed_tags(
-    existing_tags: &[PartialSemverVersion],
-    manifests: BTreeMap<PartialSemverVersion, OciManifest>,
+/// Checks that the partial tags and the latest tag point to the highest full versions of their
+/// ranges. The existing tags are given together with the digests that they point to.
+pub fn detect_miss_placed_tags(
+    digests: BTreeMap<PartialSemverVersion, String>,
+    latest: Option<LatestTag>,
+    yanked: &[Version],
 ) -> std::result::Result<(), Vec<ValidationError>> {
-    assert!(
-        existing_tags.iter().collect::<BTreeSet<_>>() == manifests.keys().collect::<BTreeSet<_>>(),
-        "Tags and manifests must be equal"
-    );
+    let existing_tags = digests.keys().collect::<Vec<_>>();
 
     let mut errors = Vec::new();
 
-    let mut duplicate_versions = BTreeMap::<Vec<u8>, Vec<Version>>::new();
-    for (version, manifest) in manifests
+    let mut duplicate_versions = BTreeMap::<&str, Vec<Version>>::new();
+    for (version, digest) in digests
         .iter()
-        .filter_map(|(psv, manifest)| Some((psv.full()?, manifest)))
+        .filter_map(|(psv, digest)| Some((psv.full()?, digest)))
     {
-        let manifest_hash = sha2::Sha256::digest(serde_json::to_string(manifest).unwrap())
-            .iter()
-            .cloned()
-            .collect::<Vec<u8>>();
         duplicate_versions
-            .entry(manifest_hash)
+            .entry(digest)
             .and_modify(|e| {
                 e.push(version.clone());
             })
@@ -106,29 +321,37 @@ fn detect_miss_placed_tags(
             .map(|versions| ValidationError::FullVersionsPointingToSameManifests { versions }),
     );
 
-    let mut manifests_grouped_by_major =
-        HashMap::<PartialSemverVersion, BTreeMap<Version, &OciManifest>>::new();
-    let mut manifests_grouped_by_major_minor =
-        HashMap::<PartialSemverVersion, BTreeMap<Version, &OciManifest>>::new();
+    let mut digests_grouped_by_major =
+        HashMap::<PartialSemverVersion, BTreeMap<Version, &str>>::new();
+    let mut digests_grouped_by_major_minor =
+        HashMap::<PartialSemverVersion, BTreeMap<Version, &str>>::new();
     let mut full_tags_without_major = BTreeMap::<PartialSemverVersion, &Version>::new();
     let mut full_tags_without_major_minor = BTreeMap::<PartialSemverVersion, &Version>::new();
 
-    for full_tag in existing_tags.iter().filter(|psv| psv.full().is_some()) {
+    // Pre-releases are not covered by the stable partial tags. Yanked versions are still grouped
+    // to find out if a partial tag points to them but they don't require partial tags.
+    for full_tag in existing_tags
+        .iter()
+        .copied()
+        .filter(|psv| psv.full().is_some_and(|version| version.pre.is_empty()))
+    {
+        let is_yanked = yanked.contains(full_tag.full_unchecked());
         let major_minor = full_tag
             .to_major_minor()
             .expect("full must be convertible to major.minor");
 
-        match manifests.get(&major_minor) {
-            Some(manifest) => {
-                manifests_grouped_by_major_minor
+        match digests.get(&major_minor) {
+            Some(digest) => {
+                digests_grouped_by_major_minor
                     .entry(major_minor)
                     .and_modify(|e| {
-                        e.insert(full_tag.full_unchecked().clone(), manifest);
+                        e.insert(full_tag.full_unchecked().clone(), digest);
                     })
                     .or_insert_with(|| {
-                        BTreeMap::from([(full_tag.full_unchecked().clone(), manifest)])
+                        BTreeMap::from([(full_tag.full_unchecked().clone(), digest.as_str())])
                     });
             }
+            None if is_yanked => {}
             None => {
                 let version = full_tag.full_unchecked();
                 full_tags_without_major_minor
@@ -143,17 +366,18 @@ fn detect_miss_placed_tags(
         }
 
         let major = full_tag.to_major();
-        match manifests.get(&major) {
-            Some(manifest) => {
-                manifests_grouped_by_major
+        match digests.get(&major) {
+            Some(digest) => {
+                digests_grouped_by_major
                     .entry(major)
                     .and_modify(|e| {
-                        e.insert(full_tag.full_unchecked().clone(), manifest);
+                        e.insert(full_tag.full_unchecked().clone(), digest);
                     })
                     .or_insert_with(|| {
-                        BTreeMap::from([(full_tag.full_unchecked().clone(), manifest)])
+                        BTreeMap::from([(full_tag.full_unchecked().clone(), digest.as_str())])
                     });
             }
+            None if is_yanked => {}
             None => {
                 let version = full_tag.full_unchecked();
                 full_tags_without_major
@@ -179,63 +403,130 @@ fn detect_miss_placed_tags(
         }
     }));
 
+    /// Checks that the partial tag points to the highest full version of its range that hasn't been
+    /// yanked. The versions are mapped to the digest that the partial tag points to. If all
+    /// versions of the range have been yanked, the partial tag may point to any of them.
     fn check_misplaced(
         partial_tag: PartialSemverVersion,
-        versions_and_manifests: BTreeMap<Version, &OciManifest>,
-        manifests: &BTreeMap<PartialSemverVersion, OciManifest>,
+        versions_and_digests: BTreeMap<Version, &str>,
+        digests: &BTreeMap<PartialSemverVersion, String>,
+        yanked: &[Version],
     ) -> Option<ValidationError> {
-        let (version, manifest) = versions_and_manifests
-            .iter()
-            .last()
-            .expect("There must be at least one entry");
-
-        match manifests.get(&PartialSemverVersion::from(version.clone())) {
-            Some(full_version_manifest) => {
-                let manifest = serde_json::to_value(manifest).unwrap();
-                let full_version_manifest = serde_json::to_value(full_version_manifest).unwrap();
-
-                if manifest != full_version_manifest {
-                    let pointing_to_instead = versions_and_manifests
-                        .iter()
-                        .rev()
-                        // we compared the last entry already
-                        .skip(1)
-                        .find_map(|(version, m)| {
-                            let m = serde_json::to_value(m).unwrap();
-                            if m == manifest {
-                                Some(version)
-                            } else {
-                                None
-                            }
-                        })
-                        .cloned()
-                        .unwrap();
-
-                    Some(ValidationError::MissPlaced {
-                        major_or_major_minor: partial_tag,
-                        should_point_to: version.clone(),
-                        pointing_to_instead,
-                    })
-                } else {
-                    None
+        let digest = *versions_and_digests.values().next()?;
+        let pointing_to = version_with_digest(digest, &versions_and_digests, digests);
+
+        let Some(version) = versions_and_digests
+            .keys()
+            .rev()
+            .find(|version| !yanked.contains(version))
+        else {
+            return match pointing_to {
+                Some(_) => None,
+                None => Some(ValidationError::OrphanedPartial {
+                    major_or_major_minor: partial_tag,
+                    digest: digest.to_string(),
+                }),
+            };
+        };
+
+        let full_version_digest = digests
+            .get(&PartialSemverVersion::from(version.clone()))
+            .expect("Digest must be present for every tag");
+        if digest == full_version_digest {
+            return None;
+        }
+
+        Some(match pointing_to {
+            Some(pointing_to_instead)
+                if partial_tag.major().is_some()
+                    && (pointing_to_instead.major, pointing_to_instead.minor)
+                        != (version.major, version.minor) =>
+            {
+                ValidationError::MajorPointsIntoOtherMinor {
+                    major: partial_tag,
+                    should_point_to: version.clone(),
+                    pointing_to_instead: pointing_to_instead.clone(),
                 }
             }
-            None => todo!(),
-        }
+            Some(pointing_to_instead) => ValidationError::MissPlaced {
+                major_or_major_minor: partial_tag,
+                should_point_to: version.clone(),
+                pointing_to_instead: pointing_to_instead.clone(),
+            },
+            None => ValidationError::PartialPointsToUnknownManifest {
+                major_or_major_minor: partial_tag,
+                should_point_to: version.clone(),
+                digest: digest.to_string(),
+            },
+        })
+    }
+
+    /// The full version whose manifest has the given digest, preferring the highest version of the
+    /// partial tag's range over versions of other ranges.
+    fn version_with_digest<'a>(
+        digest: &str,
+        versions_and_digests: &'a BTreeMap<Version, &str>,
+        digests: &'a BTreeMap<PartialSemverVersion, String>,
+    ) -> Option<&'a Version> {
+        versions_and_digests
+            .keys()
+            .rev()
+            .find(|version| {
+                digests
+                    .get(&PartialSemverVersion::from((*version).clone()))
+                    .is_some_and(|d| d == digest)
+            })
+            .or_else(|| {
+                digests
+                    .iter()
+                    .rev()
+                    .filter_map(|(psv, d)| Some((psv.full()?, d)))
+                    .find(|(_, d)| *d == digest)
+                    .map(|(version, _)| version)
+            })
     }
 
-    errors.extend(manifests_grouped_by_major.into_iter().filter_map(
-        |(major, versions_and_manifests)| {
-            check_misplaced(major, versions_and_manifests, &manifests)
+    errors.extend(digests_grouped_by_major.into_iter().filter_map(
+        |(major, versions_and_digests)| {
+            check_misplaced(major, versions_and_digests, &digests, yanked)
         },
     ));
 
-    errors.extend(manifests_grouped_by_major_minor.into_iter().filter_map(
-        |(major_minor, versions_and_manifests)| {
-            check_misplaced(major_minor, versions_and_manifests, &manifests)
+    errors.extend(digests_grouped_by_major_minor.into_iter().filter_map(
+        |(major_minor, versions_and_digests)| {
+            check_misplaced(major_minor, versions_and_digests, &digests, yanked)
         },
     ));
 
+    let stable_versions = existing_tags
+        .iter()
+        .filter_map(|psv| psv.full())
+        .filter(|version| version.pre.is_empty())
+        .collect::<BTreeSet<_>>();
+    let stable_partial_tags = existing_tags
+        .iter()
+        .copied()
+        .filter(|psv| psv.full().is_none() && psv.channel().is_none())
+        .collect::<BTreeSet<_>>();
+
+    errors.extend(
+        stable_partial_tags
+            .iter()
+            .copied()
+            .filter(|partial_tag| {
+                !stable_versions
+                    .iter()
+                    .any(|version| in_range(partial_tag, version))
+            })
+            .map(|partial_tag| ValidationError::DanglingPartial {
+                major_or_major_minor: partial_tag.clone(),
+            }),
+    );
+
+    if let Some(latest) = latest {
+        errors.extend(check_latest(&digests, latest, yanked));
+    }
+
     if errors.is_empty() {
         Ok(())
     } else {
@@ -243,44 +534,164 @@ fn detect_miss_placed_tags(
     }
 }
 
-async fn fetch_manifests(
-    client: &Client,
-    registry_auth: &RegistryAuth,
+/// Checks that the latest tag points to the highest stable version of the given ones that hasn't
+/// been yanked.
+pub(crate) fn check_latest(
+    digests: &BTreeMap<PartialSemverVersion, String>,
+    latest: LatestTag,
+    yanked: &[Version],
+) -> Option<ValidationError> {
+    let (version, digest) = digests
+        .iter()
+        .rev()
+        .filter_map(|(psv, digest)| Some((psv.full()?, digest)))
+        .find(|(version, _)| version.pre.is_empty() && !yanked.contains(version))?;
+
+    match latest.digest {
+        None => Some(ValidationError::MissingLatest {
+            latest_tag: latest.name,
+            latest_version: version.clone(),
+        }),
+        Some(latest_digest) if digest != &latest_digest => {
+            let pointing_to_instead = digests
+                .iter()
+                .filter_map(|(psv, d)| Some((psv.full()?, d)))
+                .find(|(_, d)| *d == &latest_digest)
+                .map(|(version, _)| version.clone());
+
+            Some(ValidationError::MissPlacedLatest {
+                latest_tag: latest.name,
+                should_point_to: version.clone(),
+                pointing_to_instead,
+            })
+        }
+        Some(_) => None,
+    }
+}
+
+/// Whether the stable version belongs to the range of the major or major.minor tag.
+fn in_range(partial_tag: &PartialSemverVersion, version: &Version) -> bool {
+    let version = PartialSemverVersion::from(version.clone());
+    match partial_tag {
+        PartialSemverVersion::Major(_) => version.to_major() == *partial_tag,
+        PartialSemverVersion::MajorMinor(_) => version
+            .to_major_minor()
+            .is_ok_and(|major_minor| major_minor == *partial_tag),
+        PartialSemverVersion::Full(_) => false,
+    }
+}
+
+/// Resolves the digest of a tag that may not exist (yet), e.g. the `latest` tag.
+pub(crate) async fn fetch_optional_digest<R: Registry>(
+    registry: &R,
+    image: &Reference,
+    tag: &str,
+) -> Result<Option<String>> {
+    let tagged_image = Reference::from_str(&format!(
+        "{}/{}:{tag}",
+        image.registry(),
+        image.repository(),
+    ))
+    .expect("Must be valid image string");
+
+    registry.resolve_digest(&tagged_image).await
+}
+
+pub(crate) fn is_manifest_unknown(err: &OciDistributionError) -> bool {
+    match err {
+        OciDistributionError::ImageManifestNotFoundError(_) => true,
+        OciDistributionError::ServerError { code, .. } => *code == 404,
+        OciDistributionError::RegistryError { envelope, .. } => envelope
+            .errors
+            .iter()
+            .any(|e| e.code == OciErrorCode::ManifestUnknown),
+        _ => false,
+    }
+}
+
+/// Resolves the digests of the manifests that the tags point to. The OCI registry reads them from
+/// the `Docker-Content-Digest` header of `HEAD` requests so that validating repositories with many
+/// releases doesn't download (and count as pull of) every manifest. Only if the registry omits the
+/// header, the manifest is pulled and its digest computed.
+pub(crate) async fn fetch_digests<R: Registry>(
+    registry: &R,
     image: &Reference,
     tag_prefix: &Option<String>,
+    tag_suffix: &Option<String>,
     existing_tags: &[PartialSemverVersion],
-) -> Result<BTreeMap<PartialSemverVersion, OciManifest>> {
+) -> Result<BTreeMap<PartialSemverVersion, String>> {
     let mut set = JoinSet::new();
 
     for tag in existing_tags.iter().cloned() {
         let tagged_image = Reference::from_str(&format!(
-            "{}/{}:{}{tag}",
+            "{}/{}:{}{tag}{}",
             image.registry(),
             image.repository(),
-            tag_prefix.as_ref().map(|t| t.as_str()).unwrap_or("")
+            tag_prefix.as_ref().map(|t| t.as_str()).unwrap_or(""),
+            tag_suffix.as_ref().map(|t| t.as_str()).unwrap_or("")
         ))
         .expect("Must be valid image string");
 
-        let auth = registry_auth.clone();
-        let client = client.clone();
-        set.spawn(async move { (tag, client.pull_manifest(&tagged_image, &auth).await) });
+        let registry = registry.clone();
+        set.spawn(async move {
+            let digest = registry
+                .resolve_digest(&tagged_image)
+                .await
+                .and_then(|digest| {
+                    digest.ok_or_else(|| anyhow!("{tagged_image} has been deleted meanwhile"))
+                });
+            (tag, digest)
+        });
     }
 
-    let mut manifests = BTreeMap::new();
+    let mut digests = BTreeMap::new();
     while let Some(res) = set.join_next().await {
         match res {
-            Ok((tag, Ok((manifest, _digest)))) => {
-                manifests.insert(tag, manifest);
+            Ok((tag, Ok(digest))) => {
+                digests.insert(tag, digest);
             }
             Ok((tag, Err(err))) => {
-                eprintln!("Cannot fetch manifest of {image}:{tag}: {err}");
-                return Err(err).with_context(|| format!("{image}"));
+                eprintln!("Cannot fetch manifest of {image}:{tag}: {err:#}");
+                return Err(err.context(format!("{image}")));
+            }
+            Err(err) => return Err(crate::Error::Join(err).into()),
+        }
+    }
+
+    Ok(digests)
+}
+
+/// Resolves the digests of the manifests that arbitrary tags of the repository of `image` point to.
+/// Tags that have been deleted meanwhile are left out.
+pub(crate) async fn fetch_tag_digests<R: Registry>(
+    registry: &R,
+    image: &Reference,
+    tags: Vec<String>,
+) -> Result<BTreeMap<String, String>> {
+    let mut set = JoinSet::new();
+
+    for tag in tags {
+        let registry = registry.clone();
+        let image = image.clone();
+        set.spawn(async move {
+            let digest = fetch_optional_digest(&registry, &image, &tag).await;
+            (tag, digest)
+        });
+    }
+
+    let mut digests = BTreeMap::new();
+    while let Some(res) = set.join_next().await {
+        match res {
+            Ok((tag, Ok(Some(digest)))) => {
+                digests.insert(tag, digest);
             }
-            Err(_) => todo!(),
+            Ok((_, Ok(None))) => {}
+            Ok((tag, Err(err))) => return Err(err.context(format!("{image}:{tag}"))),
+            Err(err) => return Err(crate::Error::Join(err).into()),
         }
     }
 
-    Ok(manifests)
+    Ok(digests)
 }
 
 #[cfg(test)]
@@ -289,483 +700,28 @@ mod tests {
     use pretty_assertions::assert_eq;
     use std::collections::BTreeMap;
 
-    fn nextcloud_32_0_0_manifest() -> OciManifest {
-        serde_json::from_value(serde_json::json!({
-           "schemaVersion": 2,
-           "mediaType": "application/vnd.oci.image.index.v1+json",
-           "manifests": [
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5336,
-                 "digest": "sha256:ad214130d3ab539033e757ef16485b6e3478bc56fbc127c27f9ae089b11fa648",
-                 "platform": {
-                    "architecture": "amd64",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:705d08959c87babcaaa22f934f3f681ef246726c597a4b65c8d666998f3af12b",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5337,
-                 "digest": "sha256:61b6490a46b5fdc5133e43d39abfc69505e84eaceef8452aa628e99f0a5afc76",
-                 "platform": {
-                    "architecture": "arm",
-                    "os": "linux",
-                    "variant": "v5"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:ccff9e7f1dcde97cb0eb656562978c7f57099eb543f4d8f4940fe67cd9083d44",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5337,
-                 "digest": "sha256:6a3387ea92f651133babffaf4039e298de32a24599d85cd4005f332f55b1bbe7",
-                 "platform": {
-                    "architecture": "arm",
-                    "os": "linux",
-                    "variant": "v7"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:72a12507a9474f82502d7f60944adfc5838cea7f5f29174a905d96fee04e264a",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:cd94cd8deb7621fd4d934848bf88211ed25233352dfaafeed5f58df9b1a7c329",
-                 "platform": {
-                    "architecture": "arm64",
-                    "os": "linux",
-                    "variant": "v8"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:e8e7a727202936ad20142a0186a82ccbe3a61d6fbe8326ef7b88c217a0041b69",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5335,
-                 "digest": "sha256:944baa02504e4bc62dab37567fc8d2620d98d61a595c4ec5a6440ba0fa8e4dae",
-                 "platform": {
-                    "architecture": "386",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:2079bdc1437ea51330763f3c04a97a766e4faf6a2d228c9505407a420c6ff19a",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:31965a20e25da29a3f37d70f7bb29555e1aa505a60ad7d27e54e5a8df545f5fb",
-                 "platform": {
-                    "architecture": "ppc64le",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:80227f8be29ec253075989cde7d908f9055d09054cebeefa9fe1aef7555ea785",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:a2cbc703357d6672d4bda00d5fa09c1d9ec8bb062e634259e9fc852f3f7cbcb0",
-                 "platform": {
-                    "architecture": "riscv64",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:7b3b0888e4e76f2909e7749049a2b670151808a6bc8184a1f015f777f65dfc10",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5335,
-                 "digest": "sha256:8f5bb6b196128783ec2cf6c957c636f5be778c791cfb291090cfc0853d74c073",
-                 "platform": {
-                    "architecture": "s390x",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:4e12b0d80c90d74a0b418b757a5b95bba06b86a090d9e96ca05bd2008830696c",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              }
-           ]
-        })).unwrap()
-    }
-
-    fn nextcloud_32_0_1_manifest() -> OciManifest {
-        serde_json::from_value(serde_json::json!({
-           "schemaVersion": 2,
-           "mediaType": "application/vnd.oci.image.index.v1+json",
-           "manifests": [
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5336,
-                 "digest": "sha256:d2c9c96ce7a38c61674a2e0389e75c47e9820250b00f77137238014417a31201",
-                 "platform": {
-                    "architecture": "amd64",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:cd8550c2485dc05d543a48bdf5ad5680b6499963cc503adaf4af17289e630aa3",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5337,
-                 "digest": "sha256:fd47d1cee1d5359a38851d1229e6c6862d719f34700bcd4fbbc72af377b2e2f0",
-                 "platform": {
-                    "architecture": "arm",
-                    "os": "linux",
-                    "variant": "v5"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:447fa72bac35bb0cba8c9a896506c42764420f0b9bcdd5c2111f1d5a061aa58c",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5337,
-                 "digest": "sha256:bca3cd131cc42f847bd93d94c52dc416f6389ad1e5c79f16105f1c2dae680abc",
-                 "platform": {
-                    "architecture": "arm",
-                    "os": "linux",
-                    "variant": "v7"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:5532e2ce9d0cd8999ba49b68cea7eec53aa2fdaa70b6c631e8b459d8ea842da4",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:7d2c1ebed7ad8bfe233c6f7f909e4664f8990719d67108a82a9bf6c1505fc0ce",
-                 "platform": {
-                    "architecture": "arm64",
-                    "os": "linux",
-                    "variant": "v8"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:2f071b4956263871c24e7e2b66a8d3ba8fadd1fb38ebb8f2babe3c322e458eb7",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5335,
-                 "digest": "sha256:50debca8924498cda5312a52952729d1b5f65e9e74eb99c5593e4b74edd2dc43",
-                 "platform": {
-                    "architecture": "386",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:70097b7e839ef998c93da1ce41207a7e9278ba52706438399b9d720b8bcc88a0",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:2de96383f2ff44a0205af9654af4782e4680d67741715a6b01031d8a16ae0733",
-                 "platform": {
-                    "architecture": "ppc64le",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:a2efd1525dfb5f07594cb19ebf16907a4ea4e5bf72fef9cec0e68159cad5d1d0",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:e3d7b70185b5d7e76826b244730a72a1e45842c58b60e1d6aab00f58cc3bbbd6",
-                 "platform": {
-                    "architecture": "riscv64",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:47d1128981dd789dd0d0ddf5e70ef25deffd24e8bfb08635277c2626ee6d4aca",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5335,
-                 "digest": "sha256:93671b9b15e0a69d555093fca0f9dac1e2441228300fb3fc763478179a4a8a4f",
-                 "platform": {
-                    "architecture": "s390x",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:d684110391b649b32139fbce1a7124be26f517c19b656e3dfb7e0e74c324894e",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              }
-           ]
-        })).unwrap()
-    }
-
-    fn nextcloud_32_0_2_manifest() -> OciManifest {
-        serde_json::from_value(serde_json::json!({
-           "schemaVersion": 2,
-           "mediaType": "application/vnd.oci.image.index.v1+json",
-           "manifests": [
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5336,
-                 "digest": "sha256:4f0ea71dbe72cae030fa8de9dda67c728eab189becf27e40f6a380c47789ac47",
-                 "platform": {
-                    "architecture": "amd64",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:586fab872e04a76eab9c8e4e6623e65fbb95a779eae7620c25af3ff963f77c71",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5337,
-                 "digest": "sha256:b4c50ac8a940fcf4fda405323035ef03c26b59572625f9a9d9f71970ecde8769",
-                 "platform": {
-                    "architecture": "arm",
-                    "os": "linux",
-                    "variant": "v5"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:572cf2fa3c346620e0ef2efeadf9f0341a550550418c66a60666514a553b93f7",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5337,
-                 "digest": "sha256:58f656f5a7682e92540cd3ffeebfb91a50d8d992d62a49b8c720cb3eb2905620",
-                 "platform": {
-                    "architecture": "arm",
-                    "os": "linux",
-                    "variant": "v7"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:7a580b58547e8eab894ba8167d0bf4f5e00fb6b32f74e3add0969adfc856c9af",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:ffa8ff44fb01593706611c1eea21f35cc429fb9ff43d502ccf38de77665d7c8c",
-                 "platform": {
-                    "architecture": "arm64",
-                    "os": "linux",
-                    "variant": "v8"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:6c0e5336d9542889e8b079a495ec34628d871543a7760b010cde2e5786ef47ff",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5335,
-                 "digest": "sha256:401c178e39664d27a50d77c4d8f512d8ee98ca1315adb35541c8209266e13261",
-                 "platform": {
-                    "architecture": "386",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:a1dad8a6958bd4e04f6b5b766893db39b8c51736f8397e646f9e4aaec0b22c30",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:a3c712461c3821c76343ff044078f9e812c700930d72e26fada4634eccdff7bb",
-                 "platform": {
-                    "architecture": "ppc64le",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:8adfb3ee4f8559b616cd38cfe3d32f9a514dbb91f802f49408a50180cace0b05",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5338,
-                 "digest": "sha256:0bd042ed7b64a9c1ce0ff38e100d0626d5727601d0ae67447d868ffc73af005b",
-                 "platform": {
-                    "architecture": "riscv64",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:9ebc4d8e79499c0957c3c8370d8c8bc6c6664c7d2b7d2fe585a73d8997e79715",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 5335,
-                 "digest": "sha256:8d03aa09137f89ad4fcd7e8c74a889aaffba2683b08e770a9de000a8de6fdc11",
-                 "platform": {
-                    "architecture": "s390x",
-                    "os": "linux"
-                 }
-              },
-              {
-                 "mediaType": "application/vnd.oci.image.manifest.v1+json",
-                 "size": 567,
-                 "digest": "sha256:544d912a0031fb5aaab39d152d13b67e2fb337c1537158e188a9bfed6747056e",
-                 "platform": {
-                    "architecture": "unknown",
-                    "os": "unknown"
-                 }
-              }
-           ]
-        })).unwrap()
+    fn nextcloud_32_0_0_digest() -> String {
+        String::from("sha256:cd90bda7fdb550626c900dd95119b5cce2bb4c4c2e4e97ad21a1747d52f87289")
+    }
+
+    fn nextcloud_32_0_1_digest() -> String {
+        String::from("sha256:7d0a349a38b10aacddddbe01beb9d3d6d96ecf0c1797ac62dfec501148da43fd")
+    }
+
+    fn nextcloud_32_0_2_digest() -> String {
+        String::from("sha256:97e9a514809fdbb215ce3ff24f2dda52464cbf31a009d90a75cbeddd226e3f81")
     }
 
     #[test]
     fn detect_missisng_partial_semver_tags() {
         assert_eq!(
             detect_miss_placed_tags(
-                &[PartialSemverVersion::from(Version::new(32, 0, 1))],
                 BTreeMap::from([(
                     PartialSemverVersion::from(Version::new(32, 0, 1)),
-                    nextcloud_32_0_1_manifest(),
+                    nextcloud_32_0_1_digest(),
                 )]),
+                None,
+                &[],
             ),
             Err(vec![
                 ValidationError::MissingMajor {
@@ -782,30 +738,26 @@ mod tests {
     fn detect_missing_major_only() {
         assert_eq!(
             detect_miss_placed_tags(
-                &[
-                    PartialSemverVersion::with_major_minor(32, 0),
-                    PartialSemverVersion::from(Version::new(32, 0, 0)),
-                    PartialSemverVersion::from(Version::new(32, 0, 1)),
-                    PartialSemverVersion::from(Version::new(32, 0, 2)),
-                ],
                 BTreeMap::from([
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 0)),
-                        nextcloud_32_0_0_manifest(),
+                        nextcloud_32_0_0_digest(),
                     ),
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 1)),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 2)),
-                        nextcloud_32_0_2_manifest(),
+                        nextcloud_32_0_2_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major_minor(32, 0),
-                        nextcloud_32_0_2_manifest(),
+                        nextcloud_32_0_2_digest(),
                     )
                 ]),
+                None,
+                &[],
             ),
             Err(vec![ValidationError::MissingMajor {
                 latest_version: Version::new(32, 0, 2)
@@ -817,25 +769,22 @@ mod tests {
     fn detect_missing_major_minor_only() {
         assert_eq!(
             detect_miss_placed_tags(
-                &[
-                    PartialSemverVersion::with_major(32),
-                    PartialSemverVersion::from(Version::new(32, 0, 0)),
-                    PartialSemverVersion::from(Version::new(32, 0, 1))
-                ],
                 BTreeMap::from([
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 0)),
-                        nextcloud_32_0_0_manifest(),
+                        nextcloud_32_0_0_digest(),
                     ),
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 1)),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major(32),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     )
                 ]),
+                None,
+                &[],
             ),
             Err(vec![ValidationError::MissingMajorMinor {
                 latest_version: Version::new(32, 0, 1)
@@ -847,31 +796,27 @@ mod tests {
     fn detect_miss_placed_major() {
         assert_eq!(
             detect_miss_placed_tags(
-                &[
-                    PartialSemverVersion::with_major(32),
-                    PartialSemverVersion::with_major_minor(32, 0),
-                    PartialSemverVersion::from(Version::new(32, 0, 0)),
-                    PartialSemverVersion::from(Version::new(32, 0, 1))
-                ],
                 BTreeMap::from([
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 0)),
-                        nextcloud_32_0_0_manifest(),
+                        nextcloud_32_0_0_digest(),
                     ),
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 1)),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major_minor(32, 0),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major(32),
                         // should have been 32_0_1
-                        nextcloud_32_0_0_manifest(),
+                        nextcloud_32_0_0_digest(),
                     )
                 ]),
+                None,
+                &[],
             ),
             Err(vec![ValidationError::MissPlaced {
                 major_or_major_minor: PartialSemverVersion::with_major(32),
@@ -885,31 +830,27 @@ mod tests {
     fn detect_miss_placed_major_minor() {
         assert_eq!(
             detect_miss_placed_tags(
-                &[
-                    PartialSemverVersion::with_major(32),
-                    PartialSemverVersion::with_major_minor(32, 0),
-                    PartialSemverVersion::from(Version::new(32, 0, 0)),
-                    PartialSemverVersion::from(Version::new(32, 0, 1))
-                ],
                 BTreeMap::from([
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 0)),
-                        nextcloud_32_0_0_manifest(),
+                        nextcloud_32_0_0_digest(),
                     ),
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 1)),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major_minor(32, 0),
                         // should have been 32_0_1
-                        nextcloud_32_0_0_manifest(),
+                        nextcloud_32_0_0_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major(32),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     )
                 ]),
+                None,
+                &[],
             ),
             Err(vec![ValidationError::MissPlaced {
                 major_or_major_minor: PartialSemverVersion::with_major_minor(32, 0),
@@ -923,35 +864,456 @@ mod tests {
     fn detect_duplicate_versions() {
         assert_eq!(
             detect_miss_placed_tags(
-                &[
-                    PartialSemverVersion::with_major(32),
-                    PartialSemverVersion::with_major_minor(32, 0),
-                    PartialSemverVersion::from(Version::new(32, 0, 0)),
-                    PartialSemverVersion::from(Version::new(32, 0, 1))
-                ],
                 BTreeMap::from([
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 0)),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::from(Version::new(32, 0, 1)),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major_minor(32, 0),
                         // should have been 32_0_1
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     ),
                     (
                         PartialSemverVersion::with_major(32),
-                        nextcloud_32_0_1_manifest(),
+                        nextcloud_32_0_1_digest(),
                     )
                 ]),
+                None,
+                &[],
             ),
             Err(vec![ValidationError::FullVersionsPointingToSameManifests {
                 versions: vec![Version::new(32, 0, 0), Version::new(32, 0, 1),]
             },])
         );
     }
+
+    #[test]
+    fn ignore_pre_releases_for_stable_partial_tags() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 0)),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::from_str("32.0.1-rc.1").unwrap(),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        nextcloud_32_0_0_digest(),
+                    )
+                ]),
+                None,
+                &[],
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn detect_miss_placed_latest() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 0)),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 1)),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        nextcloud_32_0_1_digest(),
+                    )
+                ]),
+                Some(LatestTag {
+                    name: String::from("latest"),
+                    // should have been 32_0_1
+                    digest: Some(nextcloud_32_0_0_digest()),
+                }),
+                &[],
+            ),
+            Err(vec![ValidationError::MissPlacedLatest {
+                latest_tag: String::from("latest"),
+                should_point_to: Version::new(32, 0, 1),
+                pointing_to_instead: Some(Version::new(32, 0, 0))
+            },])
+        );
+    }
+
+    #[test]
+    fn detect_missing_latest() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 1)),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        nextcloud_32_0_1_digest(),
+                    )
+                ]),
+                Some(LatestTag {
+                    name: String::from("latest"),
+                    digest: None,
+                }),
+                &[],
+            ),
+            Err(vec![ValidationError::MissingLatest {
+                latest_tag: String::from("latest"),
+                latest_version: Version::new(32, 0, 1),
+            },])
+        );
+    }
+
+    #[test]
+    fn accept_partial_tags_rolled_back_from_yanked_version() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 1)),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 2)),
+                        nextcloud_32_0_2_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        nextcloud_32_0_1_digest(),
+                    )
+                ]),
+                Some(LatestTag {
+                    name: String::from("latest"),
+                    digest: Some(nextcloud_32_0_1_digest()),
+                }),
+                &[Version::new(32, 0, 2)],
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn detect_partial_tag_pointing_to_yanked_version() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 1)),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 2)),
+                        nextcloud_32_0_2_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        // should have been rolled back to 32_0_1
+                        nextcloud_32_0_2_digest(),
+                    )
+                ]),
+                None,
+                &[Version::new(32, 0, 2)],
+            ),
+            Err(vec![ValidationError::MissPlaced {
+                major_or_major_minor: PartialSemverVersion::with_major(32),
+                should_point_to: Version::new(32, 0, 1),
+                pointing_to_instead: Version::new(32, 0, 2)
+            },])
+        );
+    }
+
+    #[test]
+    fn accept_range_with_yanked_versions_only() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([(
+                    PartialSemverVersion::from(Version::new(32, 0, 2)),
+                    nextcloud_32_0_2_digest(),
+                )]),
+                None,
+                &[Version::new(32, 0, 2)],
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn detect_partial_tag_pointing_to_unknown_manifest() {
+        let hotfix_digest =
+            String::from("sha256:0000000000000000000000000000000000000000000000000000000000000000");
+
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 0)),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        hotfix_digest.clone(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        nextcloud_32_0_0_digest(),
+                    )
+                ]),
+                None,
+                &[],
+            ),
+            Err(vec![ValidationError::PartialPointsToUnknownManifest {
+                major_or_major_minor: PartialSemverVersion::with_major_minor(32, 0),
+                should_point_to: Version::new(32, 0, 0),
+                digest: hotfix_digest,
+            }])
+        );
+    }
+
+    #[test]
+    fn detect_partial_tag_pointing_into_other_range() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 0)),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::from(Version::new(32, 1, 0)),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 1),
+                        // should have been 32_1_0
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        nextcloud_32_0_1_digest(),
+                    )
+                ]),
+                None,
+                &[],
+            ),
+            Err(vec![
+                ValidationError::MissingMajorMinor {
+                    latest_version: Version::new(32, 0, 0)
+                },
+                ValidationError::MissPlaced {
+                    major_or_major_minor: PartialSemverVersion::with_major_minor(32, 1),
+                    should_point_to: Version::new(32, 1, 0),
+                    pointing_to_instead: Version::new(32, 0, 0)
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn detect_orphaned_partial_tag_of_yanked_range() {
+        let hotfix_digest =
+            String::from("sha256:0000000000000000000000000000000000000000000000000000000000000000");
+
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 2)),
+                        nextcloud_32_0_2_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        hotfix_digest.clone(),
+                    ),
+                ]),
+                None,
+                &[Version::new(32, 0, 2)],
+            ),
+            Err(vec![ValidationError::OrphanedPartial {
+                major_or_major_minor: PartialSemverVersion::with_major_minor(32, 0),
+                digest: hotfix_digest,
+            }])
+        );
+    }
+
+    #[test]
+    fn detect_dangling_partial_tags() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::with_major_minor(3, 1),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(3),
+                        nextcloud_32_0_0_digest(),
+                    )
+                ]),
+                None,
+                &[],
+            ),
+            Err(vec![
+                ValidationError::DanglingPartial {
+                    major_or_major_minor: PartialSemverVersion::with_major_minor(3, 1),
+                },
+                ValidationError::DanglingPartial {
+                    major_or_major_minor: PartialSemverVersion::with_major(3),
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn detect_major_pointing_into_other_minor() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 0)),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::from(Version::new(32, 1, 0)),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 1),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        // should have been 32_1_0
+                        nextcloud_32_0_0_digest(),
+                    )
+                ]),
+                None,
+                &[],
+            ),
+            Err(vec![ValidationError::MajorPointsIntoOtherMinor {
+                major: PartialSemverVersion::with_major(32),
+                should_point_to: Version::new(32, 1, 0),
+                pointing_to_instead: Version::new(32, 0, 0),
+            }])
+        );
+    }
+
+    #[test]
+    fn report_dangling_higher_minor_only_once() {
+        assert_eq!(
+            detect_miss_placed_tags(
+                BTreeMap::from([
+                    (
+                        PartialSemverVersion::from(Version::new(32, 0, 0)),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 0),
+                        nextcloud_32_0_0_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major_minor(32, 1),
+                        nextcloud_32_0_1_digest(),
+                    ),
+                    (
+                        PartialSemverVersion::with_major(32),
+                        nextcloud_32_0_0_digest(),
+                    )
+                ]),
+                None,
+                &[],
+            ),
+            Err(vec![ValidationError::DanglingPartial {
+                major_or_major_minor: PartialSemverVersion::with_major_minor(32, 1),
+            }])
+        );
+    }
+
+    #[test]
+    fn report_miss_placed_with_affixes() {
+        let error = ValidationError::MissPlaced {
+            major_or_major_minor: PartialSemverVersion::with_major(32),
+            should_point_to: Version::new(32, 0, 1),
+            pointing_to_instead: Version::new(32, 0, 0),
+        };
+
+        assert_eq!(
+            serde_json::to_value(error.report(&Some(String::from("v")), &None)).unwrap(),
+            serde_json::json!({
+                "kind": "miss_placed",
+                "partial_tag": "v32",
+                "expected_version": "32.0.1",
+                "actual_version": "32.0.0",
+                "versions": [],
+                "message": "The 32 tag points to 32.0.0 instead to 32.0.1"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_in_memory_registry() {
+        let registry = crate::registry::InMemoryRegistry::default();
+        for (tag, digest) in [
+            ("32.0.0", nextcloud_32_0_0_digest()),
+            ("32.0.1", nextcloud_32_0_1_digest()),
+            ("32.0", nextcloud_32_0_0_digest()),
+            ("32", nextcloud_32_0_1_digest()),
+        ] {
+            registry.insert(
+                &Reference::from_str(&format!("localhost:5000/nextcloud:{tag}")).unwrap(),
+                crate::registry::image_manifest(&digest),
+            );
+        }
+        let image = Reference::from_str("localhost:5000/nextcloud").unwrap();
+        let existing_tags = crate::present_partial_semver_tags(&registry, &image, &None, &None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            validation_errors(&registry, &image, &None, &None, &None, &[], &existing_tags)
+                .await
+                .unwrap(),
+            vec![ValidationError::MissPlaced {
+                major_or_major_minor: PartialSemverVersion::with_major_minor(32, 0),
+                should_point_to: Version::new(32, 0, 1),
+                pointing_to_instead: Version::new(32, 0, 0)
+            }]
+        );
+    }
 }
@@ -0,0 +1,215 @@
+use crate::{
+    registry::Registry,
+    tag::{TagReport, TagStatus},
+    validate::{
+        check_latest, detect_miss_placed_tags, fetch_digests, fetch_optional_digest, LatestTag,
+        ValidationError,
+    },
+    PartialSemverVersion,
+};
+use anyhow::Result;
+use oci_client::Reference;
+use semver::Version;
+use std::collections::HashMap;
+
+/// Checks that the tags of the major version line of `version` as well as the latest tag are
+/// consistent after the push, because a successful push doesn't guarantee that the repository is
+/// consistent afterwards, e.g. if another pipeline pushed concurrently. The pushed tags have been
+/// resolved and compared with the source digest by [`crate::tag::tag_into`] already, thus their
+/// digests are taken from the report instead of resolving them again.
+#[allow(clippy::too_many_arguments)]
+pub async fn verify<R: Registry>(
+    registry: &R,
+    image: &Reference,
+    report: &TagReport,
+    version: &Version,
+    tag_prefix: &Option<String>,
+    tag_suffix: &Option<String>,
+    latest: &Option<String>,
+    yanked: &[Version],
+) -> Result<Vec<ValidationError>> {
+    if report.source_digest.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let pushed_digests = report
+        .tags
+        .iter()
+        .filter(|tag| tag.status == TagStatus::Pushed)
+        .filter_map(|tag| Some((tag.tag.as_str(), tag.digest.as_ref()?)))
+        .collect::<HashMap<_, _>>();
+    let pushed_digest = |tag: &PartialSemverVersion| {
+        pushed_digests
+            .get(
+                format!(
+                    "{}{tag}{}",
+                    tag_prefix.as_deref().unwrap_or(""),
+                    tag_suffix.as_deref().unwrap_or("")
+                )
+                .as_str(),
+            )
+            .map(|digest| (*digest).clone())
+    };
+
+    let mut errors = Vec::new();
+
+    let existing_tags =
+        crate::present_partial_semver_tags(registry, image, tag_prefix, tag_suffix).await?;
+    let highest_stable_version = highest_stable_version(&existing_tags, yanked);
+    let (pushed, not_pushed): (Vec<_>, Vec<_>) = major_line(existing_tags, version)
+        .into_iter()
+        .partition(|tag| pushed_digest(tag).is_some());
+    let mut digests = fetch_digests(registry, image, tag_prefix, tag_suffix, &not_pushed).await?;
+    digests.extend(pushed.into_iter().filter_map(|tag| {
+        let digest = pushed_digest(&tag)?;
+        Some((tag, digest))
+    }));
+    if let Err(inconsistencies) = detect_miss_placed_tags(digests.clone(), None, yanked) {
+        errors.extend(inconsistencies);
+    }
+
+    if let Some(name) = latest {
+        // The highest version may belong to another major version line.
+        if let Some(highest) = highest_stable_version.filter(|v| !digests.contains_key(v)) {
+            digests
+                .extend(fetch_digests(registry, image, tag_prefix, tag_suffix, &[highest]).await?);
+        }
+        let digest = match pushed_digests.get(name.as_str()) {
+            Some(digest) => Some((*digest).clone()),
+            None => fetch_optional_digest(registry, image, name).await?,
+        };
+        let latest = LatestTag {
+            name: name.clone(),
+            digest,
+        };
+        errors.extend(check_latest(&digests, latest, yanked));
+    }
+
+    Ok(errors)
+}
+
+/// The highest stable version that hasn't been yanked, i.e. the version that the latest tag must
+/// point to.
+fn highest_stable_version(
+    existing_tags: &[PartialSemverVersion],
+    yanked: &[Version],
+) -> Option<PartialSemverVersion> {
+    existing_tags
+        .iter()
+        .filter(|psv| {
+            psv.full()
+                .is_some_and(|v| v.pre.is_empty() && !yanked.contains(v))
+        })
+        .max()
+        .cloned()
+}
+
+/// The tags that belong to the major version of `version`, i.e. the major tag and all major.minor
+/// and full version tags below it.
+fn major_line(
+    existing_tags: Vec<PartialSemverVersion>,
+    version: &Version,
+) -> Vec<PartialSemverVersion> {
+    existing_tags
+        .into_iter()
+        .filter(|tag| tag.to_major().major_unchecked().major == version.major)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn restrict_verification_to_major_line() {
+        let existing_tags = ["1", "1.0", "1.0.0", "2", "2.1", "2.1.0", "2.1.1", "21.0.0"]
+            .into_iter()
+            .map(|tag| PartialSemverVersion::from_str(tag).unwrap())
+            .collect();
+
+        assert_eq!(
+            major_line(existing_tags, &Version::new(2, 1, 1)),
+            ["2", "2.1", "2.1.0", "2.1.1"]
+                .into_iter()
+                .map(|tag| PartialSemverVersion::from_str(tag).unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn latest_points_to_highest_version_that_is_not_yanked() {
+        let existing_tags = ["2", "2.1", "2.1.0", "3.0.0", "3.1.0-rc.1"]
+            .into_iter()
+            .map(|tag| PartialSemverVersion::from_str(tag).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            highest_stable_version(&existing_tags, &[Version::new(3, 0, 0)]),
+            Some(PartialSemverVersion::from(Version::new(2, 1, 0)))
+        );
+    }
+
+    #[tokio::test]
+    async fn accept_tags_rolled_back_from_yanked_version() {
+        use crate::registry::{image, registry_with_tags};
+
+        let registry = registry_with_tags(&[
+            ("16.7.0", "sha256:167"),
+            ("16.8.0", "sha256:168"),
+            ("16.8.1", "sha256:1681"),
+            ("16.8", "sha256:168"),
+            ("16", "sha256:168"),
+            ("latest", "sha256:168"),
+        ]);
+        let report = crate::tag::tag(
+            &registry,
+            &image("16.7.0"),
+            vec![String::from("16.7")],
+            false,
+        )
+        .await
+        .unwrap();
+
+        let verify = |yanked: Vec<Version>| {
+            let registry = registry.clone();
+            let report = &report;
+            async move {
+                verify(
+                    &registry,
+                    &image("16.7.0"),
+                    report,
+                    &Version::new(16, 7, 0),
+                    &None,
+                    &None,
+                    &Some(String::from("latest")),
+                    &yanked,
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        assert_eq!(verify(vec![Version::new(16, 8, 1)]).await, Vec::new());
+        assert_eq!(
+            verify(Vec::new()).await,
+            vec![
+                ValidationError::MissPlaced {
+                    major_or_major_minor: PartialSemverVersion::with_major(16),
+                    should_point_to: Version::new(16, 8, 1),
+                    pointing_to_instead: Version::new(16, 8, 0),
+                },
+                ValidationError::MissPlaced {
+                    major_or_major_minor: PartialSemverVersion::with_major_minor(16, 8),
+                    should_point_to: Version::new(16, 8, 1),
+                    pointing_to_instead: Version::new(16, 8, 0),
+                },
+                ValidationError::MissPlacedLatest {
+                    latest_tag: String::from("latest"),
+                    should_point_to: Version::new(16, 8, 1),
+                    pointing_to_instead: Some(Version::new(16, 8, 0)),
+                },
+            ]
+        );
+    }
+}